@@ -1,11 +1,12 @@
 use crate::fmt::*;
+use crate::markdown::code_info_string;
 use crate::text_pos::*;
 use regex::RegexBuilder;
-use regex::{Captures, Match, Regex};
+use regex::{Captures, Regex};
+use std::fmt::Write;
 use std::sync::OnceLock;
 use std::{ops::Range, path::Path};
 use thiserror::Error;
-use yansi::Paint;
 
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub struct Attr<'a> {
@@ -14,8 +15,33 @@ pub struct Attr<'a> {
     pub kind: Kind,
     pub action: Action,
     pub arg: ActionArg<'a>,
+    pub options: Vec<AttrOption<'a>>,
 }
 
+/// The ranges of the parts of an attribute in the input text, for diagnostics.
+#[derive(Clone, Default, Debug)]
+pub struct AttrSpans {
+    /// The path, including the quotes.
+    pub path: Range<usize>,
+    /// The action with its argument, such as `start("text")` or `region("usage")`.
+    pub action: Range<usize>,
+    /// The argument of the action with its parentheses, if any.
+    pub arg: Option<Range<usize>>,
+    /// The options, in the same order as [`Attr::options`].
+    pub options: Vec<Range<usize>>,
+}
+
+const ACTIONS: &[&str] = &["start", "end", "region", "section", "item", "anchor"];
+const OPTIONS: &[&str] = &[
+    "hash",
+    "exclude_heading",
+    "heading_offset",
+    "code",
+    "hide",
+    "style",
+];
+const STYLES: &[&str] = &["comment", "attr", "raw", "block"];
+
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
 pub enum Kind {
     Inner,
@@ -28,6 +54,44 @@ impl Kind {
             Kind::Outer => "/// ",
         }
     }
+    pub fn block_doc_comment_start(self) -> &'static str {
+        match self {
+            Kind::Inner => "/*!",
+            Kind::Outer => "/**",
+        }
+    }
+    /// The `!` after `#` in doc attributes of this kind.
+    pub fn attr_bang(self) -> &'static str {
+        match self {
+            Kind::Inner => "!",
+            Kind::Outer => "",
+        }
+    }
+}
+
+/// The form of the generated documentation, specified by `style("...")`.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[non_exhaustive]
+pub enum DocStyle {
+    /// `/// text` or `//! text`.
+    Comment,
+    /// `#[doc = " text"]` or `#![doc = " text"]` for each line.
+    Attr,
+    /// A single `#[doc = r"..."]` or `#![doc = r"..."]` with a raw string literal.
+    RawAttr,
+    /// A block doc comment, `/** ... */` or `/*! ... */`.
+    Block,
+}
+impl DocStyle {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "comment" => Some(DocStyle::Comment),
+            "attr" => Some(DocStyle::Attr),
+            "raw" => Some(DocStyle::RawAttr),
+            "block" => Some(DocStyle::Block),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
@@ -42,8 +106,106 @@ pub enum ActionArg<'a> {
     Line(usize),
     LineRev(usize),
     Text(&'a str),
+    /// `region("{name}")`: the lines between `<!-- region: {name} -->` and `<!-- endregion -->`.
+    Region(&'a str),
+    /// `section("{path}")`: the section under the heading, such as `section("Install/From source")`.
+    Section(&'a str),
+    /// `item("{signature}")`: the body of a Rust item, such as `item("fn main")`.
+    Item(&'a str),
+    /// `anchor("{name}")`: the lines between `// ANCHOR: {name}` and `// ANCHOR_END: {name}`.
+    Anchor(&'a str),
+}
+impl ActionArg<'_> {
+    /// Returns `true` if this argument selects a part of the file by itself, instead of specifying its start.
+    pub fn is_selector(&self) -> bool {
+        matches!(
+            self,
+            ActionArg::Region(_)
+                | ActionArg::Section(_)
+                | ActionArg::Item(_)
+                | ActionArg::Anchor(_)
+        )
+    }
+    /// Returns a description of the selected part for error messages, such as ``region `usage` ``.
+    pub fn selector_description(&self) -> Option<String> {
+        match self {
+            ActionArg::Region(name) => Some(format!("region `{name}`")),
+            ActionArg::Section(path) => Some(format!("section `{path}`")),
+            ActionArg::Item(signature) => Some(format!("item `{signature}`")),
+            ActionArg::Anchor(name) => Some(format!("anchor `{name}`")),
+            _ => None,
+        }
+    }
 }
 
+/// An argument after the action, such as `hash("...")`.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct AttrOption<'a> {
+    pub name: &'a str,
+    pub value: OptionValue<'a>,
+}
+impl AttrOption<'_> {
+    fn is_valid(&self) -> bool {
+        match (self.name, self.value) {
+            ("hide", OptionValue::Text(pattern)) => Regex::new(pattern).is_ok(),
+            ("style", OptionValue::Text(style)) => DocStyle::from_name(style).is_some(),
+            (name, value) => matches!(
+                (name, value),
+                ("hash", OptionValue::Text(_))
+                    | ("exclude_heading", OptionValue::None)
+                    | ("heading_offset", OptionValue::Int(_))
+                    | ("code", OptionValue::None | OptionValue::Text(_))
+                    | ("hide", OptionValue::None)
+            ),
+        }
+    }
+    /// Returns the help message for an invalid argument of this option.
+    fn help(&self) -> String {
+        if let ("style", OptionValue::Text(style)) = (self.name, self.value) {
+            if let Some(similar) = find_similar(style, STYLES.iter().copied()) {
+                return format!("did you mean `style(\"{similar}\")`?");
+            }
+        }
+        usage_of_option(self.name)
+    }
+}
+
+/// Returns the help message showing the valid forms of the action `name`.
+fn usage_of_action(name: &str) -> String {
+    match name {
+        "start" | "end" => format!(
+            "expected `{name}`, `{name}({{line}})`, `{name}(-{{line}})` or `{name}(\"{{text}}\")`"
+        ),
+        "section" => "expected `section(\"{heading}/{heading}\")`".into(),
+        "item" => "expected `item(\"{signature}\")`, such as `item(\"fn main\")`".into(),
+        _ => format!("expected `{name}(\"{{name}}\")`"),
+    }
+}
+
+/// Returns the help message showing the valid forms of the option `name`.
+fn usage_of_option(name: &str) -> String {
+    match name {
+        "hash" => "expected `hash(\"{hash}\")`".into(),
+        "exclude_heading" => "`exclude_heading` takes no argument".into(),
+        "heading_offset" => "expected an integer, such as `heading_offset(1)`".into(),
+        "code" => "expected `code` or `code(\"{info string}\")`".into(),
+        "hide" => "expected `hide` or `hide(\"{regex}\")` with a valid regular expression".into(),
+        _ => {
+            let styles: Vec<_> = STYLES.iter().map(|s| format!("style(\"{s}\")")).collect();
+            let styles: Vec<_> = styles.iter().map(|s| s.as_str()).collect();
+            format!("expected {}", fmt_one_of(&styles))
+        }
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum OptionValue<'a> {
+    None,
+    Int(isize),
+    Text(&'a str),
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
 pub enum Mismatch {
     Kind,
     Path,
@@ -51,8 +213,8 @@ pub enum Mismatch {
 impl Mismatch {
     pub fn message(&self) -> &'static str {
         match self {
-            Mismatch::Kind => "mismatch attribute kind.",
-            Mismatch::Path => "mismatch include path.",
+            Mismatch::Kind => "the kind of the end attribute does not match the start attribute",
+            Mismatch::Path => "the path of the end attribute does not match the start attribute",
         }
     }
 }
@@ -61,43 +223,205 @@ fn attr_regex() -> &'static Regex {
     static RE: OnceLock<Regex> = OnceLock::new();
     RE.get_or_init(|| {
         RegexBuilder::new(
-            r#"(?m:^[ \t]*//[ \t]*#(!?)\[[ \t]*include_doc(?:[ \t]*\([ \t]*"([^"]*)"[ \t]*,[ \t]*(start|end)[ \t]*(?:\([ \t]*(?:"([^"]*)"|(-)?([0-9]+))[ \t]*\)[ \t]*)?\)[ \t]*|.*)\][ \t]*$)"#,
-        ).crlf(true).build().unwrap()
+            r#"(?m:^[ \t]*//[ \t]*#(!?)\[[ \t]*include_doc(?:[ \t]*\((.*)\)[ \t]*|.*)\][ \t]*$)"#,
+        )
+        .crlf(true)
+        .build()
+        .unwrap()
     })
 }
 
 impl<'a> Attr<'a> {
-    pub fn from_captures(c: &Captures<'a>) -> Option<Self> {
-        let target = match c.get(1)?.as_str() {
+    pub(crate) fn from_captures(c: &Captures<'a>) -> Result<Self, BadAttrError> {
+        Self::parse(c, 0).map(|(attr, _)| attr)
+    }
+    /// Parses the attribute in `c` along with the ranges of its parts. `base` is added to the ranges.
+    fn parse(c: &Captures<'a>, base: usize) -> Result<(Self, AttrSpans), BadAttrError> {
+        let m = c.get(0).unwrap();
+        let range = base + m.start()..base + m.end();
+        let error = |span: Range<usize>, message: String, help: Option<String>| BadAttrError {
+            range: range.clone(),
+            span,
+            message,
+            help,
+        };
+        let kind = match &c[1] {
             "" => Kind::Outer,
             "!" => Kind::Inner,
             _ => unreachable!(),
         };
-        let path = c.get(2)?.as_str();
-        let kind = match c.get(3)?.as_str() {
-            "start" => Action::Start,
-            "end" => Action::End,
-            _ => unreachable!(),
+        let Some(args) = c.get(2) else {
+            return Err(error(
+                range.clone(),
+                "expected the arguments of `include_doc`".into(),
+                Some(
+                    "add the path and the action, such as `include_doc(\"{path}\", start)`".into(),
+                ),
+            ));
         };
-        let arg = if let Some(c) = c.get(4) {
-            ActionArg::Text(c.as_str())
-        } else if let Some(c5) = c.get(6) {
-            let value = c5.as_str().parse().ok()?;
-            if c.get(5).is_some() {
-                ActionArg::LineRev(value)
-            } else {
-                ActionArg::Line(value)
+        let mut p = ArgsParser::new(args.as_str(), base + args.start());
+        let path_start = p.next_offset();
+        let Some(path) = p.string() else {
+            return Err(error(
+                p.next_span(),
+                "expected the path of the file to include as a string literal".into(),
+                None,
+            ));
+        };
+        let mut spans = AttrSpans {
+            path: path_start..p.offset(),
+            ..AttrSpans::default()
+        };
+        if !p.eat(',') {
+            return Err(error(
+                p.next_span(),
+                "expected `,` after the path".into(),
+                None,
+            ));
+        }
+        let action_start = p.next_offset();
+        let Some(name) = p.ident() else {
+            return Err(error(
+                p.next_span(),
+                "expected an action".into(),
+                Some(format!("expected {}", fmt_one_of(ACTIONS))),
+            ));
+        };
+        let name_span = action_start..p.offset();
+        let arg_start = p.next_offset();
+        let (action, arg) = match name {
+            "start" => (Action::Start, p.action_arg()),
+            "end" => (Action::End, p.action_arg()),
+            "region" => (Action::Start, p.selector_arg().map(ActionArg::Region)),
+            "section" => (Action::Start, p.selector_arg().map(ActionArg::Section)),
+            "item" => (Action::Start, p.selector_arg().map(ActionArg::Item)),
+            "anchor" => (Action::Start, p.selector_arg().map(ActionArg::Anchor)),
+            _ => {
+                return Err(error(
+                    name_span,
+                    format!("unknown action `{name}`"),
+                    Some(fmt_suggestion(name, ACTIONS)),
+                ))
             }
-        } else {
-            ActionArg::None
         };
-        Some(Self {
-            range: c.get(0)?.range(),
-            kind: target,
+        let Some(arg) = arg else {
+            return Err(error(
+                arg_start..p.next_span().end,
+                format!("invalid argument of `{name}`"),
+                Some(usage_of_action(name)),
+            ));
+        };
+        spans.action = action_start..p.offset();
+        spans.arg = (p.offset() > arg_start).then(|| arg_start..p.offset());
+        let mut options: Vec<AttrOption> = Vec::new();
+        while p.eat(',') {
+            let option_start = p.next_offset();
+            let Some(name) = p.ident() else {
+                return Err(error(
+                    p.next_span(),
+                    "expected an option".into(),
+                    Some(format!("expected {}", fmt_one_of(OPTIONS))),
+                ));
+            };
+            if !OPTIONS.contains(&name) {
+                return Err(error(
+                    option_start..p.offset(),
+                    format!("unknown option `{name}`"),
+                    Some(fmt_suggestion(name, OPTIONS)),
+                ));
+            }
+            let Some(value) = p.option_value() else {
+                return Err(error(
+                    option_start..p.next_span().end,
+                    format!("invalid argument of option `{name}`"),
+                    Some(usage_of_option(name)),
+                ));
+            };
+            let option = AttrOption { name, value };
+            let option_span = option_start..p.offset();
+            if !option.is_valid() {
+                return Err(error(
+                    option_span,
+                    format!("invalid argument of option `{name}`"),
+                    Some(option.help()),
+                ));
+            }
+            if options.iter().any(|o| o.name == name) {
+                return Err(error(
+                    option_span,
+                    format!("option `{name}` is specified more than once"),
+                    Some("remove one of them".into()),
+                ));
+            }
+            options.push(option);
+            spans.options.push(option_span);
+        }
+        if !p.finish() {
+            let rest = p.offset()..p.end;
+            return Err(error(
+                rest,
+                format!("unexpected `{}`", p.s.trim_end()),
+                Some("options are separated by `,`".into()),
+            ));
+        }
+        let attr = Self {
+            range,
+            kind,
             path,
-            action: kind,
+            action,
             arg,
-        })
+            options,
+        };
+        Ok((attr, spans))
+    }
+    pub fn option(&self, name: &str) -> Option<OptionValue<'a>> {
+        self.options
+            .iter()
+            .find(|o| o.name == name)
+            .map(|o| o.value)
+    }
+    /// The number of levels to add to the headings in the included text.
+    pub fn heading_offset(&self) -> Option<isize> {
+        match self.option("heading_offset")? {
+            OptionValue::Int(offset) => Some(offset),
+            _ => None,
+        }
+    }
+    /// The info string of the code block to wrap the included text in.
+    ///
+    /// If `code` has no argument, the info string is determined from the extension of the included file.
+    pub fn code(&self) -> Option<&'a str> {
+        match self.option("code")? {
+            OptionValue::Text(info_string) => Some(info_string),
+            _ => Some(code_info_string(
+                Path::new(self.path)
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .unwrap_or_default(),
+            )),
+        }
+    }
+    /// The pattern of the lines to hide with rustdoc's `# ` prefix, if `hide` is specified.
+    ///
+    /// If `hide` has no argument, only the lines between `// hide-start` and `// hide-end` are hidden.
+    pub fn hide(&self) -> Option<Option<Regex>> {
+        match self.option("hide")? {
+            OptionValue::Text(pattern) => Some(Regex::new(pattern).ok()),
+            _ => Some(None),
+        }
+    }
+    pub fn doc_style(&self) -> Option<DocStyle> {
+        match self.option("style")? {
+            OptionValue::Text(style) => DocStyle::from_name(style),
+            _ => None,
+        }
+    }
+    /// The hash of the text generated last time, recorded in the end attribute.
+    pub fn hash(&self) -> Option<&'a str> {
+        match self.option("hash")? {
+            OptionValue::Text(hash) => Some(hash),
+            _ => None,
+        }
     }
     pub fn mismatch(&self, other: &Self) -> Option<Mismatch> {
         if self.kind != other.kind {
@@ -113,42 +437,195 @@ impl<'a> Attr<'a> {
     }
 
     pub fn find_iter(text: &'a str) -> impl Iterator<Item = Result<Attr<'a>, BadAttrError>> {
-        attr_regex().captures_iter(text).map(|c| {
-            Self::from_captures(&c).ok_or_else(|| BadAttrError::from_match(c.get(0).unwrap()))
-        })
+        attr_regex()
+            .captures_iter(text)
+            .map(|c| Self::from_captures(&c))
     }
 
-    pub fn message(&self, rel_path: &Path, input: &str) -> String {
-        format!(
-            "{}\n{}",
-            fmt_link(rel_path, self.line(input)),
-            fmt_source(vec![("", &input[self.range()])]),
-        )
+    /// Returns the ranges of the parts of this attribute in `input`.
+    pub fn spans(&self, input: &str) -> AttrSpans {
+        attr_regex()
+            .captures(&input[self.range()])
+            .and_then(|c| Attr::parse(&c, self.range.start).ok())
+            .map_or_else(AttrSpans::default, |(_, spans)| spans)
+    }
+    /// Returns the range of the option `name` in `input`, or the range of this attribute if it is not specified.
+    pub fn option_span(&self, input: &str, name: &str) -> Range<usize> {
+        self.options
+            .iter()
+            .zip(self.spans(input).options)
+            .find(|(o, _)| o.name == name)
+            .map_or_else(|| self.range(), |(_, span)| span)
     }
     pub fn line(&self, input: &str) -> usize {
         to_line(input, self.range.start)
     }
+    /// The whitespace before `//` on the line of this attribute.
+    pub fn indent<'b>(&self, input: &'b str) -> &'b str {
+        let s = &input[self.range()];
+        &s[..s.len() - s.trim_start().len()]
+    }
+
+    /// Formats this attribute as a comment, without leading whitespace.
+    pub fn to_source(&self) -> String {
+        let mut s = format!(
+            "// #{}[include_doc(\"{}\", ",
+            match self.kind {
+                Kind::Inner => "!",
+                Kind::Outer => "",
+            },
+            self.path,
+        );
+        if !self.arg.is_selector() {
+            s.push_str(match self.action {
+                Action::Start => "start",
+                Action::End => "end",
+            });
+        }
+        match self.arg {
+            ActionArg::None => {}
+            ActionArg::Line(line) => write!(s, "({line})").unwrap(),
+            ActionArg::LineRev(line) => write!(s, "(-{line})").unwrap(),
+            ActionArg::Text(text) => write!(s, "(\"{text}\")").unwrap(),
+            ActionArg::Region(name) => write!(s, "region(\"{name}\")").unwrap(),
+            ActionArg::Section(path) => write!(s, "section(\"{path}\")").unwrap(),
+            ActionArg::Item(signature) => write!(s, "item(\"{signature}\")").unwrap(),
+            ActionArg::Anchor(name) => write!(s, "anchor(\"{name}\")").unwrap(),
+        }
+        for option in &self.options {
+            write!(s, ", {}", option.name).unwrap();
+            match option.value {
+                OptionValue::None => {}
+                OptionValue::Int(value) => write!(s, "({value})").unwrap(),
+                OptionValue::Text(text) => write!(s, "(\"{text}\")").unwrap(),
+            }
+        }
+        s.push_str(")]");
+        s
+    }
+}
+
+/// Parses the arguments of `include_doc(...)`.
+struct ArgsParser<'a> {
+    s: &'a str,
+    /// The offset of the end of the arguments in the input text.
+    end: usize,
+}
+impl<'a> ArgsParser<'a> {
+    fn new(s: &'a str, start: usize) -> Self {
+        Self {
+            s,
+            end: start + s.len(),
+        }
+    }
+    /// The offset of the rest of the arguments in the input text.
+    fn offset(&self) -> usize {
+        self.end - self.s.len()
+    }
+    /// The offset of the next token in the input text.
+    fn next_offset(&mut self) -> usize {
+        self.skip_ws();
+        self.offset()
+    }
+    /// The range of the next character in the input text, which is empty at the end of the arguments.
+    fn next_span(&mut self) -> Range<usize> {
+        let start = self.next_offset();
+        start..start + self.s.chars().next().map_or(0, char::len_utf8)
+    }
+    fn skip_ws(&mut self) {
+        self.s = self.s.trim_start_matches([' ', '\t']);
+    }
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_ws();
+        if let Some(s) = self.s.strip_prefix(c) {
+            self.s = s;
+            true
+        } else {
+            false
+        }
+    }
+    fn expect(&mut self, c: char) -> Option<()> {
+        self.eat(c).then_some(())
+    }
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> Option<&'a str> {
+        self.skip_ws();
+        let len = self.s.find(|c| !f(c)).unwrap_or(self.s.len());
+        if len == 0 {
+            return None;
+        }
+        let (value, s) = self.s.split_at(len);
+        self.s = s;
+        Some(value)
+    }
+    fn ident(&mut self) -> Option<&'a str> {
+        self.take_while(|c| c.is_ascii_alphanumeric() || c == '_')
+    }
+    fn number(&mut self) -> Option<usize> {
+        self.take_while(|c| c.is_ascii_digit())?.parse().ok()
+    }
+    fn string(&mut self) -> Option<&'a str> {
+        self.skip_ws();
+        let s = self.s.strip_prefix('"')?;
+        let len = s.find('"')?;
+        self.s = &s[len + 1..];
+        Some(&s[..len])
+    }
+    fn action_arg(&mut self) -> Option<ActionArg<'a>> {
+        if !self.eat('(') {
+            return Some(ActionArg::None);
+        }
+        let arg = if let Some(text) = self.string() {
+            ActionArg::Text(text)
+        } else if self.eat('-') {
+            ActionArg::LineRev(self.number()?)
+        } else {
+            ActionArg::Line(self.number()?)
+        };
+        self.expect(')')?;
+        Some(arg)
+    }
+    fn selector_arg(&mut self) -> Option<&'a str> {
+        self.expect('(')?;
+        let value = self.string()?;
+        self.expect(')')?;
+        Some(value)
+    }
+    fn option_value(&mut self) -> Option<OptionValue<'a>> {
+        if !self.eat('(') {
+            return Some(OptionValue::None);
+        }
+        let value = if let Some(text) = self.string() {
+            OptionValue::Text(text)
+        } else if self.eat('-') {
+            OptionValue::Int(-isize::try_from(self.number()?).ok()?)
+        } else {
+            OptionValue::Int(isize::try_from(self.number()?).ok()?)
+        };
+        self.expect(')')?;
+        Some(value)
+    }
+    fn finish(&mut self) -> bool {
+        self.skip_ws();
+        self.s.is_empty()
+    }
 }
 
 #[derive(Error, Debug, PartialEq, Eq)]
-#[error("invalid attribute.")]
+#[error("{message}")]
 pub struct BadAttrError {
     range: Range<usize>,
+    /// The invalid part of the attribute.
+    span: Range<usize>,
+    message: String,
+    help: Option<String>,
 }
 impl BadAttrError {
-    fn from_match(m: Match) -> Self {
-        Self { range: m.range() }
-    }
-    pub fn message(&self, rel_path: &Path, input: &str) -> String {
-        let p = TextPos::from_str_offset(input, self.range.start);
-        format!(
-            r"invalid attribute
-{}
- {} {}",
-            fmt_link(rel_path, p.line),
-            "|".cyan().bold(),
-            &input[self.range()]
-        )
+    /// The range of the invalid part of the attribute.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+    pub fn help(&self) -> Option<&str> {
+        self.help.as_deref()
     }
     pub fn range(&self) -> Range<usize> {
         self.range.clone()
@@ -165,6 +642,7 @@ mod tests {
             path,
             action,
             arg,
+            options: Vec::new(),
         };
         let c = attr_regex()
             .captures(s)
@@ -255,6 +733,49 @@ mod tests {
         );
     }
 
+    #[test]
+    fn attr_arg_region() {
+        attr_check(
+            r#"// #[include_doc("abc", region("usage"))]"#,
+            Kind::Outer,
+            "abc",
+            Action::Start,
+            ActionArg::Region("usage"),
+        );
+    }
+
+    #[test]
+    fn attr_arg_section() {
+        attr_check(
+            r#"// #[include_doc("abc", section("Install/From source"))]"#,
+            Kind::Outer,
+            "abc",
+            Action::Start,
+            ActionArg::Section("Install/From source"),
+        );
+    }
+
+    #[test]
+    fn attr_arg_item() {
+        attr_check(
+            r#"// #[include_doc("abc", item("fn main"))]"#,
+            Kind::Outer,
+            "abc",
+            Action::Start,
+            ActionArg::Item("fn main"),
+        );
+    }
+    #[test]
+    fn attr_arg_anchor() {
+        attr_check(
+            r#"// #[include_doc("abc", anchor("setup"))]"#,
+            Kind::Outer,
+            "abc",
+            Action::Start,
+            ActionArg::Anchor("setup"),
+        );
+    }
+
     #[test]
     fn attr_space_arg_none() {
         attr_check(
@@ -276,8 +797,147 @@ mod tests {
         );
     }
 
-    fn check_find_iter(text: &str, expected: Vec<Result<Attr, BadAttrError>>) {
-        let items: Vec<_> = Attr::find_iter(text).collect();
+    #[test]
+    fn attr_option_hash() {
+        let s = r#"// #[include_doc("abc", end, hash("0123456789abcdef"))]"#;
+        let c = attr_regex().captures(s).unwrap();
+        let attr = Attr::from_captures(&c).unwrap();
+        assert_eq!(attr.hash(), Some("0123456789abcdef"));
+        assert_eq!(attr.to_source(), s);
+    }
+    #[test]
+    fn attr_option_code() {
+        let parse = |s| Attr::from_captures(&attr_regex().captures(s).unwrap()).unwrap();
+        let attr = parse(r#"// #[include_doc("../examples/basic.rs", start, code)]"#);
+        assert_eq!(attr.code(), Some("rust"));
+        let attr = parse(r#"// #[include_doc("run.sh", start, code("console"))]"#);
+        assert_eq!(attr.code(), Some("console"));
+        let attr = parse(r#"// #[include_doc("run.sh", start)]"#);
+        assert_eq!(attr.code(), None);
+    }
+    #[test]
+    fn attr_option_hide() {
+        let parse = |s| Attr::from_captures(&attr_regex().captures(s).unwrap()).unwrap();
+        let attr = parse(r#"// #[include_doc("main.rs", start, hide)]"#);
+        assert!(matches!(attr.hide(), Some(None)));
+        let attr = parse(r#"// #[include_doc("main.rs", start, hide("^use "))]"#);
+        assert_eq!(attr.hide().unwrap().unwrap().as_str(), "^use ");
+        check_find_iter(
+            r#"// #[include_doc("abc", start, hide("("))]"#,
+            vec![Err(0..42)],
+        );
+    }
+    #[test]
+    fn attr_option_style() {
+        let parse = |s| Attr::from_captures(&attr_regex().captures(s).unwrap()).unwrap();
+        let attr = parse(r#"// #[include_doc("abc", start, style("attr"))]"#);
+        assert_eq!(attr.doc_style(), Some(DocStyle::Attr));
+        let attr = parse(r#"// #[include_doc("abc", start, style("raw"))]"#);
+        assert_eq!(attr.doc_style(), Some(DocStyle::RawAttr));
+        let attr = parse(r#"// #[include_doc("abc", start, style("block"))]"#);
+        assert_eq!(attr.doc_style(), Some(DocStyle::Block));
+        let attr = parse(r#"// #[include_doc("abc", start)]"#);
+        assert_eq!(attr.doc_style(), None);
+        check_find_iter(
+            r#"// #[include_doc("abc", start, style("x"))]"#,
+            vec![Err(0..43)],
+        );
+    }
+    #[test]
+    fn attr_option_unknown() {
+        check_find_iter(
+            r#"// #[include_doc("abc", end, unknown)]"#,
+            vec![Err(0..38)],
+        );
+    }
+    #[test]
+    fn attr_option_duplicated() {
+        check_find_iter(
+            r#"// #[include_doc("abc", end, hash("a"), hash("b"))]"#,
+            vec![Err(0..51)],
+        );
+    }
+    fn check_bad_attr(s: &str, span: &str, message: &str, help: Option<&str>) {
+        let e = Attr::find_iter(s).next().unwrap().unwrap_err();
+        assert_eq!(&s[e.span.clone()], span, "input = `{s}`");
+        assert_eq!(e.message, message);
+        assert_eq!(e.help.as_deref(), help);
+    }
+    #[test]
+    fn bad_attr_unknown_action() {
+        check_bad_attr(
+            r#"// #[include_doc("abc", strat)]"#,
+            "strat",
+            "unknown action `strat`",
+            Some("did you mean `start`?"),
+        );
+        check_bad_attr(
+            r#"// #[include_doc("abc", unknown)]"#,
+            "unknown",
+            "unknown action `unknown`",
+            Some("expected `start`, `end`, `region`, `section`, `item` or `anchor`"),
+        );
+    }
+    #[test]
+    fn bad_attr_unknown_option() {
+        check_bad_attr(
+            r#"// #[include_doc("abc", end, hsah("a"))]"#,
+            "hsah",
+            "unknown option `hsah`",
+            Some("did you mean `hash`?"),
+        );
+    }
+    #[test]
+    fn bad_attr_option_value() {
+        check_bad_attr(
+            r#"// #[include_doc("abc", start, style("blok"))]"#,
+            r#"style("blok")"#,
+            "invalid argument of option `style`",
+            Some(r#"did you mean `style("block")`?"#),
+        );
+        check_bad_attr(
+            r#"// #[include_doc("abc", start, heading_offset("1"))]"#,
+            r#"heading_offset("1")"#,
+            "invalid argument of option `heading_offset`",
+            Some("expected an integer, such as `heading_offset(1)`"),
+        );
+    }
+    #[test]
+    fn bad_attr_syntax() {
+        check_bad_attr(
+            r#"// #[include_doc(abc, start)]"#,
+            "a",
+            "expected the path of the file to include as a string literal",
+            None,
+        );
+        check_bad_attr(
+            r#"// #[include_doc("abc", start(x))]"#,
+            "(x",
+            "invalid argument of `start`",
+            Some(r#"expected `start`, `start({line})`, `start(-{line})` or `start("{text}")`"#),
+        );
+    }
+    #[test]
+    fn attr_spans() {
+        let input = "\n// #[include_doc(\"abc\", start(\"x\"), code)]\n";
+        let attr = Attr::find_iter(input).next().unwrap().unwrap();
+        let spans = attr.spans(input);
+        assert_eq!(&input[spans.path], "\"abc\"");
+        assert_eq!(&input[spans.action], "start(\"x\")");
+        assert_eq!(&input[spans.arg.unwrap()], "(\"x\")");
+        assert_eq!(&input[spans.options[0].clone()], "code");
+    }
+    #[test]
+    fn attr_to_source() {
+        let s = r#"// #![include_doc("abc", start(-3))]"#;
+        let c = attr_regex().captures(s).unwrap();
+        assert_eq!(Attr::from_captures(&c).unwrap().to_source(), s);
+    }
+
+    fn check_find_iter(text: &str, expected: Vec<Result<Attr, Range<usize>>>) {
+        let items: Vec<_> = Attr::find_iter(text)
+            .map(|item| item.map_err(|e| e.range()))
+            .collect();
         assert_eq!(items, expected);
     }
 
@@ -293,6 +953,7 @@ mod tests {
                 path: "abc",
                 action: Action::Start,
                 arg: ActionArg::None,
+                options: Vec::new(),
             })],
         );
     }
@@ -311,6 +972,7 @@ mod tests {
                     path: "abc",
                     action: Action::Start,
                     arg: ActionArg::None,
+                    options: Vec::new(),
                 }),
                 Ok(Attr {
                     range: 33..62,
@@ -318,6 +980,7 @@ mod tests {
                     path: "abc",
                     action: Action::End,
                     arg: ActionArg::None,
+                    options: Vec::new(),
                 }),
             ],
         );
@@ -329,7 +992,7 @@ mod tests {
             r#"
 // #[include_doc("abc", unknown)]
 "#,
-            vec![Err(BadAttrError { range: 1..34 })],
+            vec![Err(1..34)],
         );
     }
     #[test]
@@ -339,10 +1002,7 @@ mod tests {
 // #[include_doc("abc", unknown)]
 // #[include_doc("abc", unknown)]
 "#,
-            vec![
-                Err(BadAttrError { range: 1..34 }),
-                Err(BadAttrError { range: 35..68 }),
-            ],
+            vec![Err(1..34), Err(35..68)],
         );
     }
 
@@ -356,6 +1016,7 @@ mod tests {
                 path: "abc",
                 action: Action::Start,
                 arg: ActionArg::None,
+                options: Vec::new(),
             })],
         );
     }
@@ -0,0 +1,4 @@
+//! The entry point for `cargo rustdoc-include`.
+fn main() {
+    rustdoc_include::cli::main();
+}
@@ -0,0 +1,442 @@
+use std::{
+    env,
+    ffi::OsStr,
+    fs::write,
+    io::{stderr, stdin, IsTerminal, Read},
+    path::{Path, PathBuf},
+};
+
+use crate::{Engine, Error, LogEntry};
+use anyhow::{bail, Result};
+use cargo::Workspace;
+use clap::{Parser, Subcommand, ValueEnum};
+use config::{Config, CONFIG_FILE_NAME};
+use ignore::{overrides::Override, WalkBuilder};
+use report::Status;
+use yansi::Condition;
+
+mod cargo;
+mod config;
+mod lsp;
+mod report;
+mod watch;
+
+/// Exit status used by `--check` when some doc comments are out of sync.
+const EXIT_OUT_OF_SYNC: i32 = 2;
+
+pub fn main() {
+    use yansi::Paint;
+    match run() {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(EXIT_OUT_OF_SYNC),
+        Err(e) => {
+            eprintln!("{}: {}", "error".red().bold(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Returns `false` if `--check` found out-of-sync files.
+fn run() -> Result<bool> {
+    use yansi::Paint;
+    let mut os_args: Vec<_> = env::args_os().collect();
+    let is_subcommand = cargo::strip_subcommand(&mut os_args);
+    let args = Opt::parse_from(os_args);
+    if args.format == Format::Json {
+        yansi::disable();
+    } else {
+        // The messages are written to stderr. Diffs on stdout are checked separately.
+        yansi::whenever(Condition::from(|| {
+            Condition::os_support() && stderr().is_terminal()
+        }));
+    }
+    let workspace = if is_subcommand || !args.package.is_empty() {
+        Some(Workspace::locate()?)
+    } else {
+        None
+    };
+    let workspace_root = workspace.as_ref().map(|w| w.root.clone());
+    let config = match &args.config {
+        Some(path) => Config::read(path)?,
+        None => Config::find(
+            args.root
+                .as_deref()
+                .or(workspace_root.as_deref())
+                .unwrap_or(Path::new(".")),
+        )?,
+    };
+    let is_lsp = matches!(args.command, Some(Command::Lsp));
+    let Some(root) = args
+        .root
+        .clone()
+        .or_else(|| config.root.clone())
+        .or(workspace_root)
+        .or_else(|| is_lsp.then(|| PathBuf::from(".")))
+    else {
+        bail!("the root directory is not specified. use `--root` or set `root` in `{CONFIG_FILE_NAME}`.");
+    };
+    let root = if args.watch || is_lsp || workspace.is_some() {
+        root.canonicalize()?
+    } else {
+        root
+    };
+    let dirs = match &workspace {
+        Some(workspace) if !args.package.is_empty() => workspace.package_dirs(&args.package)?,
+        _ => vec![root.clone()],
+    };
+    let filter = config.overrides(&root)?;
+    let mut engine = config.apply(Engine::new(root).force(args.force).record_hash(args.hash))?;
+    for (name, value) in &args.define {
+        engine = engine.define(name, value);
+    }
+    if is_lsp {
+        lsp::run(&engine)?;
+        return Ok(true);
+    }
+    if args.watch {
+        return watch::watch(&engine, &args, &dirs, &filter, &config.source_roots);
+    }
+    let mut stats = Stats::default();
+    if let Some(path) = &args.stdin {
+        apply_stdin(&engine, path, &mut stats)?;
+    }
+    let paths = if args.files.is_empty() && args.stdin.is_none() {
+        rs_files(&dirs, &filter).collect::<Result<Vec<_>>>()?
+    } else {
+        args.files.clone()
+    };
+    for path in paths {
+        if args.reverse {
+            reverse_file(&engine, &args, &path, &mut stats)?;
+        } else {
+            apply_file(&engine, &args, &path, &mut stats)?;
+        }
+    }
+    if stats.error_count != 0 {
+        bail!(
+            "found {} error(s) in {} file(s)",
+            stats.error_count,
+            stats.error_files
+        );
+    }
+    if stats.stale_files != 0 {
+        eprintln!(
+            "{}: {} file(s) are out of sync. run `rustdoc-include` to update them.",
+            "check failed".red().bold(),
+            stats.stale_files
+        );
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Returns the `*.rs` files under `dirs` that are not ignored by `.gitignore` or `filter`.
+fn rs_files(dirs: &[PathBuf], filter: &Override) -> impl Iterator<Item = Result<PathBuf>> {
+    walk(dirs, filter).filter_map(|e| match e {
+        Ok(e) => {
+            if e.file_type()?.is_file() && e.path().extension() == Some(OsStr::new("rs")) {
+                Some(Ok(e.into_path()))
+            } else {
+                None
+            }
+        }
+        Err(e) => Some(Err(e.into())),
+    })
+}
+
+/// Walks the files and directories under `dirs` that are not ignored by `.gitignore` or `filter`.
+fn walk(dirs: &[PathBuf], filter: &Override) -> ignore::Walk {
+    let mut builder = WalkBuilder::new(&dirs[0]);
+    for dir in &dirs[1..] {
+        builder.add(dir);
+    }
+    builder.overrides(filter.clone());
+    builder.build()
+}
+
+#[derive(Default)]
+struct Stats {
+    stale_files: usize,
+    error_files: usize,
+    error_count: usize,
+}
+
+/// Applies `include_doc` regions in `path` and reports the result.
+///
+/// Returns the canonical paths of the included files, or `None` if the file has errors.
+fn apply_file(
+    engine: &Engine,
+    args: &Opt,
+    path: &Path,
+    stats: &mut Stats,
+) -> Result<Option<Vec<PathBuf>>> {
+    use yansi::Paint;
+    let rel_path = engine.rel_path(path);
+    match engine.apply_file_with_logs(path) {
+        Ok(result) => {
+            if args.diff {
+                print!("{}", result.to_diff(rel_path));
+            }
+            let sources = result
+                .logs
+                .iter()
+                .flat_map(|log| {
+                    let nested = log.nested_source_paths.iter().cloned();
+                    std::iter::once(log.source_path.clone()).chain(nested)
+                })
+                .collect();
+            let status = match (&result.text, args.check) {
+                (None, _) => Status::Unchanged,
+                (Some(_), true) => Status::Stale,
+                (Some(_), false) => Status::Updated,
+            };
+            match args.format {
+                Format::Json => report::print_file(rel_path, status, &result.logs, &[])?,
+                Format::Human => print_ambiguous_texts(rel_path, &result.logs),
+            }
+            match (status, result.text) {
+                (Status::Stale, _) => {
+                    stats.stale_files += 1;
+                    if args.format == Format::Human {
+                        eprintln!("{}: {}", "stale".yellow().bold(), rel_path.display());
+                        for log in &result.logs {
+                            if log.is_modified {
+                                eprintln!(
+                                    "  {}:{} <-- {}",
+                                    rel_path.display(),
+                                    log.line,
+                                    log.source_rel_path.display()
+                                );
+                            }
+                        }
+                    }
+                }
+                (Status::Updated, Some(text)) => {
+                    if args.format == Format::Human {
+                        eprintln!("{}: {}", "update".green().bold(), rel_path.display());
+                        for log in &result.logs {
+                            if log.is_modified {
+                                eprintln!("  <-- {}", log.source_rel_path.display());
+                            }
+                        }
+                    }
+                    if !args.dry_run {
+                        write(path, text)?;
+                    }
+                }
+                _ => {}
+            }
+            Ok(Some(sources))
+        }
+        Err(e) => {
+            stats.error_files += 1;
+            stats.error_count += e.errors.len();
+            if args.format == Format::Json {
+                // The regions without errors are still reported along with the errors.
+                report::print_file(rel_path, Status::Error, &e.logs, &e.errors)?;
+            } else {
+                print_ambiguous_texts(rel_path, &e.logs);
+                for e in &e.errors {
+                    print_error(e);
+                }
+            }
+            Ok(None)
+        }
+    }
+}
+
+/// Prints a note for each `start("...")` and `end("...")` text that matches more than once.
+fn print_ambiguous_texts(rel_path: &Path, logs: &[LogEntry]) {
+    use yansi::Paint;
+    for log in logs {
+        for a in &log.ambiguous_texts {
+            let lines: Vec<_> = a.source_lines.iter().map(|l| l.to_string()).collect();
+            eprintln!(
+                "{}: {}:{}: {:?} matches lines {} of `{}`. the first one is used.",
+                "note".bold(),
+                rel_path.display(),
+                a.line,
+                a.text,
+                lines.join(", "),
+                log.source_rel_path.display()
+            );
+        }
+    }
+}
+
+fn print_error(e: &Error) {
+    use yansi::Paint;
+    let label = format!("error[{}]", e.code());
+    eprintln!("{}: {}", label.red().bold(), e);
+}
+
+/// Applies `include_doc` regions in the text read from stdin as the content of `path`,
+/// and prints the result to stdout.
+fn apply_stdin(engine: &Engine, path: &Path, stats: &mut Stats) -> Result<()> {
+    let mut input = String::new();
+    stdin().read_to_string(&mut input)?;
+    let base = path.parent().unwrap_or(Path::new(""));
+    match engine.apply_str(base, &input) {
+        Ok(result) => print!("{}", result.text.as_deref().unwrap_or(&input)),
+        Err(errors) => {
+            stats.error_files += 1;
+            for e in errors {
+                let e = e.to_error(engine.rel_path(path), &input);
+                print_error(&e);
+                stats.error_count += 1;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes the edits made to doc comments in `path` back to the included files.
+fn reverse_file(engine: &Engine, args: &Opt, path: &Path, stats: &mut Stats) -> Result<()> {
+    use yansi::Paint;
+    let rel_path = engine.rel_path(path);
+    match engine.reverse_file(path) {
+        Ok(result) => {
+            for update in result.updates {
+                eprintln!("{}: {}", "update".green().bold(), update.rel_path.display());
+                for line in update.lines {
+                    eprintln!("  <-- {}:{}", rel_path.display(), line);
+                }
+                if !args.dry_run {
+                    write(&update.path, update.text)?;
+                }
+            }
+            // The hashes are updated after the included files are written,
+            // so that a failed write leaves the regions detected as edited.
+            if let (Some(text), false) = (result.text, args.dry_run) {
+                write(path, text)?;
+            }
+        }
+        Err(errors) => {
+            stats.error_files += 1;
+            for e in errors {
+                print_error(&e);
+                stats.error_count += 1;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug, ValueEnum)]
+enum Format {
+    /// Colored text for humans, written to stderr.
+    Human,
+    /// One JSON object per `*.rs` file with `include_doc` regions or errors, written to stdout.
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a language server on stdin and stdout, which reports errors and out-of-sync regions in
+    /// `*.rs` files open in the editor.
+    ///
+    /// The root defaults to the current directory.
+    Lsp,
+}
+
+#[derive(Parser)]
+struct Opt {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// The `*.rs` files to update, instead of all files in the root directory.
+    #[arg(conflicts_with_all = ["watch", "package"])]
+    files: Vec<PathBuf>,
+
+    /// Read the text of the `*.rs` file at PATH from stdin, and print the updated text to stdout.
+    ///
+    /// Include paths are resolved relative to PATH.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["files", "watch", "package", "check", "diff", "reverse", "dry_run"])]
+    stdin: Option<PathBuf>,
+
+    /// The directory containing `*.rs` files. Overrides `root` in the config file.
+    #[arg(long, global = true)]
+    root: Option<PathBuf>,
+
+    /// The config file to use instead of `rustdoc-include.toml` or `Cargo.toml` in the root directory.
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Define the value of `{{NAME}}` in the included text. Can be specified more than once.
+    #[arg(short = 'D', long, value_name = "NAME=VALUE", value_parser = parse_define, global = true)]
+    define: Vec<(String, String)>,
+
+    /// Only update `*.rs` files in the workspace member. Can be specified more than once.
+    #[arg(short, long = "package", value_name = "SPEC")]
+    package: Vec<String>,
+
+    #[arg(long = "dry-run")]
+    dry_run: bool,
+
+    /// Do not write files, and exit with status 2 if any doc comment is out of sync.
+    #[arg(long, conflicts_with = "watch")]
+    check: bool,
+
+    /// The format of the report of each file.
+    #[arg(long, value_enum, default_value_t = Format::Human, conflicts_with_all = ["diff", "reverse", "stdin"])]
+    format: Format,
+
+    /// Print a unified diff of the modified regions.
+    #[arg(long)]
+    diff: bool,
+
+    /// Keep running and update `*.rs` files when included files or attributes change.
+    #[arg(long)]
+    watch: bool,
+
+    /// Record a hash of the generated text in end attributes to detect regions edited by hand.
+    #[arg(long)]
+    hash: bool,
+
+    /// Overwrite regions edited by hand.
+    #[arg(long)]
+    force: bool,
+
+    /// Write the edits made to doc comments back to the included files.
+    #[arg(long, conflicts_with_all = ["watch", "check", "diff"])]
+    reverse: bool,
+}
+
+fn parse_define(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(format!("expected `NAME=VALUE`, found `{s}`")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn verify_opt() {
+        Opt::command().debug_assert();
+    }
+    #[test]
+    fn stdin_conflicts_with_files() {
+        assert!(Opt::try_parse_from(["rustdoc-include", "--stdin", "a.rs", "b.rs"]).is_err());
+        assert!(Opt::try_parse_from(["rustdoc-include", "a.rs", "b.rs"]).is_ok());
+    }
+    #[test]
+    fn lsp_subcommand() {
+        let args = Opt::try_parse_from(["rustdoc-include", "lsp", "--root", "src"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Lsp)));
+        assert!(args.files.is_empty());
+    }
+    #[test]
+    fn define() {
+        let args = Opt::try_parse_from(["rustdoc-include", "--define", "msrv=1.70", "-D", "a=b=c"])
+            .unwrap();
+        assert_eq!(
+            args.define,
+            [("msrv".into(), "1.70".into()), ("a".into(), "b=c".into())]
+        );
+        assert!(Opt::try_parse_from(["rustdoc-include", "--define", "msrv"]).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+use std::{
+    env,
+    ffi::OsString,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+/// The name of this tool as a cargo subcommand.
+const SUBCOMMAND: &str = "rustdoc-include";
+
+/// Removes the subcommand name that cargo passes as the first argument,
+/// and returns `true` if the process was invoked as `cargo rustdoc-include`.
+pub fn strip_subcommand(args: &mut Vec<OsString>) -> bool {
+    if args.get(1).is_some_and(|arg| arg == SUBCOMMAND) {
+        args.remove(1);
+        true
+    } else {
+        false
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Workspace {
+    #[serde(rename = "workspace_root")]
+    pub root: PathBuf,
+    packages: Vec<Package>,
+    workspace_members: Vec<String>,
+}
+#[derive(Debug, Deserialize)]
+struct Package {
+    id: String,
+    name: String,
+    manifest_path: PathBuf,
+}
+
+impl Workspace {
+    /// Locates the workspace containing the current directory with `cargo metadata`.
+    pub fn locate() -> Result<Self> {
+        let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+        let output = Command::new(cargo)
+            .args(["metadata", "--no-deps", "--format-version", "1"])
+            .output()
+            .context("cannot run `cargo metadata`")?;
+        if !output.status.success() {
+            bail!(
+                "`cargo metadata` failed\n{}",
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            );
+        }
+        serde_json::from_slice(&output.stdout)
+            .context("cannot parse the output of `cargo metadata`")
+    }
+
+    /// Returns the directories of the workspace members named `names`.
+    pub fn package_dirs(&self, names: &[String]) -> Result<Vec<PathBuf>> {
+        let mut dirs = Vec::new();
+        for name in names {
+            let Some(package) = self
+                .packages
+                .iter()
+                .find(|p| &p.name == name && self.workspace_members.contains(&p.id))
+            else {
+                bail!("package `{name}` is not a member of the workspace");
+            };
+            dirs.push(
+                package
+                    .manifest_path
+                    .parent()
+                    .unwrap_or(Path::new(""))
+                    .to_path_buf(),
+            );
+        }
+        Ok(dirs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip() {
+        let mut args = vec!["cargo-rustdoc-include".into(), "rustdoc-include".into()];
+        assert!(strip_subcommand(&mut args));
+        assert_eq!(args, vec![OsString::from("cargo-rustdoc-include")]);
+        assert!(!strip_subcommand(&mut args));
+    }
+    #[test]
+    fn package_dirs() {
+        let workspace: Workspace = serde_json::from_str(
+            r#"{
+                "workspace_root": "/ws",
+                "packages": [
+                    { "id": "a 0.1.0", "name": "a", "manifest_path": "/ws/a/Cargo.toml" },
+                    { "id": "b 0.1.0", "name": "b", "manifest_path": "/ws/b/Cargo.toml" }
+                ],
+                "workspace_members": ["a 0.1.0", "b 0.1.0"]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            workspace.package_dirs(&["b".into()]).unwrap(),
+            vec![PathBuf::from("/ws/b")]
+        );
+        assert!(workspace.package_dirs(&["c".into()]).is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use std::{
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+use crate::{DocStyle, Engine, LineEnding};
+use anyhow::{bail, Context, Result};
+use ignore::overrides::{Override, OverrideBuilder};
+use serde::Deserialize;
+
+pub const CONFIG_FILE_NAME: &str = "rustdoc-include.toml";
+
+/// Project-wide defaults, read from `rustdoc-include.toml` or `[package.metadata.rustdoc-include]` in `Cargo.toml`.
+///
+/// Paths are relative to the directory containing the config file.
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub root: Option<PathBuf>,
+    /// Globs of `*.rs` files to update, relative to the root.
+    pub include: Vec<String>,
+    /// Globs of `*.rs` files not to update, relative to the root.
+    pub exclude: Vec<String>,
+    /// Directories outside the root that files can be included from.
+    pub source_roots: Vec<PathBuf>,
+    pub style: Option<String>,
+    pub line_ending: Option<String>,
+    pub heading_offset: Option<isize>,
+}
+
+#[derive(Default, Deserialize)]
+struct CargoToml {
+    #[serde(default)]
+    package: CargoPackage,
+}
+#[derive(Default, Deserialize)]
+struct CargoPackage {
+    #[serde(default)]
+    metadata: CargoMetadata,
+}
+#[derive(Default, Deserialize)]
+struct CargoMetadata {
+    #[serde(rename = "rustdoc-include")]
+    rustdoc_include: Option<Config>,
+}
+
+impl Config {
+    /// Reads the config in `dir` from `rustdoc-include.toml`, or from `Cargo.toml` if it does not exist.
+    ///
+    /// Returns the default config if neither file has one.
+    pub fn find(dir: &Path) -> Result<Self> {
+        let path = dir.join(CONFIG_FILE_NAME);
+        if path.is_file() {
+            return Self::read(&path);
+        }
+        let path = dir.join("Cargo.toml");
+        if path.is_file() {
+            let cargo_toml: CargoToml = parse(&path)?;
+            if let Some(config) = cargo_toml.package.metadata.rustdoc_include {
+                return Ok(config.resolve(dir));
+            }
+        }
+        Ok(Self::default())
+    }
+
+    /// Reads `rustdoc-include.toml` at `path`.
+    pub fn read(path: &Path) -> Result<Self> {
+        let config: Self = parse(path)?;
+        Ok(config.resolve(path.parent().unwrap_or(Path::new(""))))
+    }
+
+    fn resolve(mut self, dir: &Path) -> Self {
+        self.root = self.root.map(|root| dir.join(root));
+        for root in &mut self.source_roots {
+            *root = dir.join(&root);
+        }
+        self
+    }
+
+    pub fn apply(&self, mut engine: Engine) -> Result<Engine> {
+        engine = engine.source_roots(self.source_roots.clone());
+        if let Some(style) = &self.style {
+            let Some(style) = DocStyle::from_name(style) else {
+                bail!("unknown style `{style}` in config. expected `comment`, `attr`, `raw` or `block`.");
+            };
+            engine = engine.style(style);
+        }
+        if let Some(line_ending) = &self.line_ending {
+            let Some(line_ending) = LineEnding::from_name(line_ending) else {
+                bail!("unknown line-ending `{line_ending}` in config. expected `auto`, `lf` or `crlf`.");
+            };
+            engine = engine.line_ending(line_ending);
+        }
+        if let Some(offset) = self.heading_offset {
+            engine = engine.heading_offset(offset);
+        }
+        Ok(engine)
+    }
+
+    /// Returns the filter for `*.rs` files under `root` made from `include` and `exclude`.
+    pub fn overrides(&self, root: &Path) -> Result<Override> {
+        let mut builder = OverrideBuilder::new(root);
+        for glob in &self.include {
+            builder.add(glob)?;
+        }
+        for glob in &self.exclude {
+            builder.add(&format!("!{glob}"))?;
+        }
+        Ok(builder.build()?)
+    }
+}
+
+fn parse<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<T> {
+    let text = read_to_string(path).with_context(|| format!("cannot read `{}`", path.display()))?;
+    toml::from_str(&text).with_context(|| format!("invalid config in `{}`", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_config() {
+        let config: Config = toml::from_str(
+            r#"
+root = "src"
+exclude = ["generated/**"]
+source-roots = ["../docs"]
+style = "attr"
+heading-offset = 1
+"#,
+        )
+        .unwrap();
+        let config = config.resolve(Path::new("crate"));
+        assert_eq!(config.root, Some(PathBuf::from("crate/src")));
+        assert_eq!(config.source_roots, vec![PathBuf::from("crate/../docs")]);
+        assert_eq!(config.exclude, vec!["generated/**"]);
+        assert_eq!(config.heading_offset, Some(1));
+    }
+    #[test]
+    fn parse_cargo_metadata() {
+        let cargo_toml: CargoToml = toml::from_str(
+            r#"
+[package]
+name = "a"
+
+[package.metadata.rustdoc-include]
+line-ending = "lf"
+"#,
+        )
+        .unwrap();
+        let config = cargo_toml.package.metadata.rustdoc_include.unwrap();
+        assert_eq!(config.line_ending.as_deref(), Some("lf"));
+    }
+    #[test]
+    fn unknown_field() {
+        assert!(toml::from_str::<Config>("roots = \"src\"").is_err());
+    }
+    #[test]
+    fn exclude() {
+        let config = Config {
+            exclude: vec!["generated/**".into()],
+            ..Config::default()
+        };
+        let overrides = config.overrides(Path::new("/root")).unwrap();
+        assert!(overrides.matched("/root/generated/a.rs", false).is_ignore());
+        assert!(overrides.matched("/root/src/a.rs", false).is_none());
+    }
+}
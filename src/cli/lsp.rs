@@ -0,0 +1,513 @@
+use std::{
+    collections::HashMap,
+    fs::{read_dir, read_to_string},
+    io::{stdin, stdout, BufRead, BufReader, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use crate::{attr_locations, Engine, Region};
+use anyhow::{bail, Result};
+use regex::Regex;
+use serde_json::{json, Value};
+
+/// The name shown as the source of diagnostics in editors.
+const SOURCE: &str = "rustdoc-include";
+
+/// A language server for `include_doc` attributes, speaking JSON-RPC on stdin and stdout.
+struct Server<'a> {
+    engine: &'a Engine,
+    /// The text of the open documents, keyed by URI.
+    documents: HashMap<String, String>,
+}
+
+/// Runs the language server until the client sends `exit`.
+pub fn run(engine: &Engine) -> Result<()> {
+    let mut server = Server {
+        engine,
+        documents: HashMap::new(),
+    };
+    let mut input = BufReader::new(stdin().lock());
+    while let Some(message) = read_message(&mut input)? {
+        let Some(method) = message["method"].as_str() else {
+            continue;
+        };
+        if method == "exit" {
+            break;
+        }
+        let params = &message["params"];
+        match message.get("id") {
+            Some(id) => {
+                let response = match server.request(method, params) {
+                    Some(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    None => json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": {
+                            "code": -32601,
+                            "message": format!("unknown method `{method}`"),
+                        },
+                    }),
+                };
+                write_message(&response)?;
+            }
+            None => {
+                for notification in server.notify(method, params) {
+                    write_message(&notification)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_message(input: &mut impl BufRead) -> Result<Option<Value>> {
+    let mut len = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                len = Some(value.trim().parse::<usize>()?);
+            }
+        }
+    }
+    let Some(len) = len else {
+        bail!("missing `Content-Length` header");
+    };
+    let mut body = vec![0; len];
+    input.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+fn write_message(message: &Value) -> Result<()> {
+    let body = serde_json::to_string(message)?;
+    let mut out = stdout().lock();
+    write!(out, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    out.flush()?;
+    Ok(())
+}
+
+impl Server<'_> {
+    /// Returns the result of a request, or `None` if the method is not supported.
+    fn request(&self, method: &str, params: &Value) -> Option<Value> {
+        match method {
+            "initialize" => Some(json!({
+                "capabilities": {
+                    "textDocumentSync": { "openClose": true, "change": 1, "save": true },
+                    "definitionProvider": true,
+                    "hoverProvider": true,
+                    "completionProvider": { "triggerCharacters": ["\"", "/"] },
+                    "codeActionProvider": true,
+                },
+                "serverInfo": { "name": SOURCE, "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => Some(Value::Null),
+            "textDocument/definition" => Some(self.on_document(params, Self::definition)),
+            "textDocument/hover" => Some(self.on_document(params, Self::hover)),
+            "textDocument/completion" => Some(self.on_document(params, Self::completion)),
+            "textDocument/codeAction" => Some(self.code_actions(params).unwrap_or(Value::Null)),
+            _ => None,
+        }
+    }
+
+    /// Handles a notification, and returns the notifications to send back.
+    fn notify(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.to_string(), text.to_string());
+                self.publish(uri).into_iter().collect()
+            }
+            "textDocument/didChange" => {
+                // The server asks for full sync, so the last change has the whole text.
+                let changes = params["contentChanges"].as_array();
+                if let Some(text) = changes.and_then(|c| c.last()?["text"].as_str()) {
+                    self.documents.insert(uri.to_string(), text.to_string());
+                }
+                self.publish(uri).into_iter().collect()
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                vec![publish_diagnostics(uri, Vec::new())]
+            }
+            // Included files may have changed, so every open document is checked again.
+            "textDocument/didSave" | "workspace/didChangeWatchedFiles" => self
+                .documents
+                .keys()
+                .filter_map(|uri| self.publish(uri))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Calls `f` with the document, the path and the offset of the position in `params`.
+    fn on_document(
+        &self,
+        params: &Value,
+        f: impl Fn(&Self, &str, &Path, usize) -> Option<Value>,
+    ) -> Value {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let target = self.documents.get(uri).zip(uri_to_path(uri));
+        target
+            .and_then(|(text, path)| {
+                let offset = to_offset(text, &params["position"])?;
+                f(self, text, &path, offset)
+            })
+            .unwrap_or(Value::Null)
+    }
+
+    /// Returns the notification with the diagnostics of the `*.rs` document at `uri`.
+    fn publish(&self, uri: &str) -> Option<Value> {
+        let path = uri_to_path(uri).filter(|p| p.extension().is_some_and(|e| e == "rs"))?;
+        let text = self.documents.get(uri)?;
+        let (regions, errors) = self.engine.regions(base_of(&path), text);
+        let mut diagnostics: Vec<_> = errors
+            .iter()
+            .map(|e| {
+                let d = e.to_diagnostic(text);
+                let mut message = d.summary;
+                if let Some(help) = d.help {
+                    message.push_str("\nhelp: ");
+                    message.push_str(&help);
+                }
+                json!({
+                    "range": to_range(text, d.range),
+                    "severity": 1,
+                    "code": d.kind.code(),
+                    "source": SOURCE,
+                    "message": message,
+                })
+            })
+            .collect();
+        for region in regions.iter().filter(|r| r.log.is_modified) {
+            diagnostics.push(json!({
+                "range": to_range(text, first_line(text, &region.range)),
+                "severity": 2,
+                "source": SOURCE,
+                "message": format!(
+                    "the region is out of sync with `{}`",
+                    region.log.source_rel_path.display()
+                ),
+            }));
+        }
+        for region in &regions {
+            for a in &region.log.ambiguous_texts {
+                diagnostics.push(json!({
+                    "range": to_range(text, line_range(text, a.line)),
+                    "severity": 3,
+                    "source": SOURCE,
+                    "message": format!(
+                        "{:?} matches more than once in `{}`. the first match is used.",
+                        a.text,
+                        region.log.source_rel_path.display()
+                    ),
+                }));
+            }
+        }
+        Some(publish_diagnostics(uri, diagnostics))
+    }
+
+    /// Returns the region of `text` that contains `offset` in its start or end attribute.
+    fn region_at(&self, text: &str, path: &Path, offset: usize) -> Option<Region> {
+        let (regions, _) = self.engine.regions(base_of(path), text);
+        regions.into_iter().find(|region| {
+            attr_locations(text).iter().any(|attr| {
+                attr.range.contains(&offset)
+                    && region.range.start <= attr.range.start
+                    && attr.range.end <= region.range.end
+            })
+        })
+    }
+
+    /// Goes from the path in an attribute to the included file, at the first included line.
+    fn definition(&self, text: &str, path: &Path, offset: usize) -> Option<Value> {
+        let attr = attr_locations(text)
+            .into_iter()
+            .find(|attr| attr.range.contains(&offset))?;
+        let span = attr.path_range;
+        if !(span.start..=span.end).contains(&offset) {
+            return None;
+        }
+        let (target, line) = match self.region_at(text, path, offset) {
+            Some(region) => (region.log.source_path, region.log.source_lines.start - 1),
+            None => (base_of(path).join(attr.path), 0),
+        };
+        if !target.is_file() {
+            return None;
+        }
+        let position = json!({ "line": line, "character": 0 });
+        Some(json!({
+            "uri": path_to_uri(&target),
+            "range": { "start": position, "end": position },
+        }))
+    }
+
+    /// Shows the included text when hovering over an attribute.
+    fn hover(&self, text: &str, path: &Path, offset: usize) -> Option<Value> {
+        let region = self.region_at(text, path, offset)?;
+        let log = &region.log;
+        let source = read_to_string(&log.source_path).ok()?;
+        let included = source.get(log.source_range.clone())?;
+        let status = if log.is_modified {
+            "out of sync"
+        } else {
+            "up to date"
+        };
+        let header = format!(
+            "`{}` lines {}-{} ({status})",
+            log.source_rel_path.display(),
+            log.source_lines.start,
+            log.source_lines
+                .end
+                .saturating_sub(1)
+                .max(log.source_lines.start),
+        );
+        Some(json!({
+            "contents": {
+                "kind": "markdown",
+                "value": format!("{header}\n\n---\n\n{included}"),
+            },
+        }))
+    }
+
+    /// Completes the names of files and directories in the path string of an attribute.
+    fn completion(&self, text: &str, path: &Path, offset: usize) -> Option<Value> {
+        let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let c = path_prefix_regex().captures(&text[line_start..offset])?;
+        let typed = c.get(1)?.as_str();
+        let dir = &typed[..typed.rfind('/').map_or(0, |i| i + 1)];
+        let name = &typed[dir.len()..];
+        let dir = base_of(path).join(dir);
+        let edit_range = to_range(text, offset - name.len()..offset);
+        let mut items = Vec::new();
+        for e in read_dir(dir).ok()?.flatten() {
+            let Ok(file_name) = e.file_name().into_string() else {
+                continue;
+            };
+            if file_name.starts_with('.') && !name.starts_with('.') {
+                continue;
+            }
+            let is_dir = e.file_type().is_ok_and(|t| t.is_dir());
+            let label = if is_dir {
+                format!("{file_name}/")
+            } else {
+                file_name
+            };
+            items.push(json!({
+                "label": label,
+                // Folder or File
+                "kind": if is_dir { 19 } else { 17 },
+                "textEdit": { "range": edit_range, "newText": label },
+            }));
+        }
+        Some(Value::Array(items))
+    }
+
+    /// Offers to update the out-of-sync regions in the requested range.
+    fn code_actions(&self, params: &Value) -> Option<Value> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let text = self.documents.get(uri)?;
+        let path = uri_to_path(uri)?;
+        let start = to_offset(text, &params["range"]["start"])?;
+        let end = to_offset(text, &params["range"]["end"])?;
+        let (regions, _) = self.engine.regions(base_of(&path), text);
+        let actions = regions
+            .iter()
+            .filter(|r| r.log.is_modified && r.range.start <= end && start <= r.range.end)
+            .map(|region| {
+                let edit = json!({
+                    "range": to_range(text, region.range.clone()),
+                    "newText": region.new_text,
+                });
+                let source = region.log.source_rel_path.display();
+                json!({
+                    "title": format!("Sync this region with `{source}`"),
+                    "kind": "quickfix",
+                    "edit": { "changes": { uri: [edit] } },
+                })
+            })
+            .collect();
+        Some(Value::Array(actions))
+    }
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+/// Matches the text before the cursor when it is in the path string of an attribute.
+fn path_prefix_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r#"^[ \t]*//[ \t]*#!?\[[ \t]*include_doc[ \t]*\([ \t]*"([^"]*)$"#).unwrap()
+    })
+}
+
+fn base_of(path: &Path) -> &Path {
+    path.parent().unwrap_or(Path::new(""))
+}
+
+/// Returns the range of the line whose number is `line`, without the line break.
+fn line_range(text: &str, line: usize) -> Range<usize> {
+    let start: usize = text
+        .split_inclusive('\n')
+        .take(line - 1)
+        .map(str::len)
+        .sum();
+    first_line(text, &(start..text.len()))
+}
+
+/// Returns the range of the first line of `range`, without the line break.
+fn first_line(text: &str, range: &Range<usize>) -> Range<usize> {
+    let s = &text[range.clone()];
+    let len = s.find(['\r', '\n']).unwrap_or(s.len());
+    range.start..range.start + len
+}
+
+/// Converts a byte offset to an LSP position, whose character is counted in UTF-16 code units.
+fn to_position(text: &str, offset: usize) -> Value {
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = text[..line_start].matches('\n').count();
+    let character: usize = text[line_start..offset].chars().map(char::len_utf16).sum();
+    json!({ "line": line, "character": character })
+}
+fn to_range(text: &str, range: Range<usize>) -> Value {
+    json!({ "start": to_position(text, range.start), "end": to_position(text, range.end) })
+}
+/// Converts an LSP position to a byte offset, clamping it to the end of the line.
+fn to_offset(text: &str, position: &Value) -> Option<usize> {
+    let line = usize::try_from(position["line"].as_u64()?).ok()?;
+    let character = usize::try_from(position["character"].as_u64()?).ok()?;
+    let line_start = match line {
+        0 => 0,
+        _ => text.match_indices('\n').nth(line - 1)?.0 + 1,
+    };
+    let mut units = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return Some(line_start + i);
+        }
+        units += c.len_utf16();
+    }
+    Some(text.len())
+}
+
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    let mut bytes = Vec::new();
+    let mut rest = path.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' && tail.len() >= 2 {
+            let hex = std::str::from_utf8(&tail[..2]).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    let path = String::from_utf8(bytes).ok()?;
+    // `file:///C:/dir` on Windows
+    let path = match path.as_bytes() {
+        [b'/', _, b':', ..] if cfg!(windows) => &path[1..],
+        _ => &path,
+    };
+    Some(PathBuf::from(path))
+}
+fn path_to_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from("file://");
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for b in path.bytes() {
+        if b.is_ascii_alphanumeric() || b"/-_.~:".contains(&b) {
+            uri.push(char::from(b));
+        } else {
+            uri.push_str(&format!("%{b:02X}"));
+        }
+    }
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn position_utf16() {
+        let text = "a\n\u{1F600}b\n";
+        assert_eq!(to_position(text, 7), json!({ "line": 1, "character": 3 }));
+        assert_eq!(
+            to_offset(text, &json!({ "line": 1, "character": 2 })),
+            Some(6)
+        );
+        assert_eq!(
+            to_offset(text, &json!({ "line": 0, "character": 9 })),
+            Some(1)
+        );
+    }
+    #[test]
+    fn uri() {
+        let path = Path::new("/home/a b/lib.rs");
+        let uri = path_to_uri(path);
+        assert_eq!(uri, "file:///home/a%20b/lib.rs");
+        assert_eq!(uri_to_path(&uri).as_deref(), Some(path));
+    }
+    #[test]
+    fn message() {
+        let mut input = "Content-Length: 8\r\n\r\n{\"id\":1}".as_bytes();
+        assert_eq!(read_message(&mut input).unwrap(), Some(json!({ "id": 1 })));
+        assert_eq!(read_message(&mut input).unwrap(), None);
+    }
+    #[test]
+    fn completion() {
+        let engine = Engine::new(Path::new("tests/data").canonicalize().unwrap());
+        let server = Server {
+            engine: &engine,
+            documents: HashMap::new(),
+        };
+        let path = engine.root().join("lib.rs");
+        let text = r#"// #[include_doc("doc_s"#;
+        let items = server.completion(text, &path, text.len()).unwrap();
+        let labels: Vec<_> = items
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap())
+            .collect();
+        assert!(labels.contains(&"doc_sections.md"));
+        assert!(labels.contains(&"examples/"));
+        assert_eq!(items[0]["textEdit"]["range"]["start"]["character"], 18);
+    }
+    #[test]
+    fn hover_and_definition() {
+        let engine = Engine::new(Path::new("tests/data").canonicalize().unwrap());
+        let server = Server {
+            engine: &engine,
+            documents: HashMap::new(),
+        };
+        let path = engine.root().join("lib.rs");
+        let text = "// #[include_doc(\"doc_long.md\", start(2))]\n// #[include_doc(\"doc_long.md\", end)]\n";
+        let hover = server.hover(text, &path, 5).unwrap();
+        let value = hover["contents"]["value"].as_str().unwrap();
+        assert!(value.starts_with("`doc_long.md` lines 2-5 (out of sync)"));
+        assert!(value.ends_with("B\nC\nD\nE"));
+        let location = server.definition(text, &path, 20).unwrap();
+        assert!(location["uri"].as_str().unwrap().ends_with("/doc_long.md"));
+        assert_eq!(location["range"]["start"]["line"], 1);
+        assert!(server.definition(text, &path, 5).is_none());
+    }
+}
@@ -0,0 +1,133 @@
+use std::{ops::Range, path::Path};
+
+use crate::{AmbiguousText, Error, LogEntry};
+use anyhow::Result;
+use serde::Serialize;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Unchanged,
+    Updated,
+    /// Out of sync, found by `--check`.
+    Stale,
+    Error,
+}
+
+/// The report of a `*.rs` file for `--format json`.
+#[derive(Serialize)]
+struct FileReport<'a> {
+    path: &'a Path,
+    status: Status,
+    includes: Vec<IncludeReport<'a>>,
+    errors: Vec<ErrorReport<'a>>,
+}
+
+#[derive(Serialize)]
+struct IncludeReport<'a> {
+    source: &'a Path,
+    start_line: usize,
+    end_line: usize,
+    source_range: Range<usize>,
+    source_lines: Range<usize>,
+    modified: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    ambiguous_texts: Vec<AmbiguousTextReport<'a>>,
+}
+impl<'a> IncludeReport<'a> {
+    fn new(log: &'a LogEntry) -> Self {
+        Self {
+            source: &log.source_rel_path,
+            start_line: log.line,
+            end_line: log.end_line,
+            source_range: log.source_range.clone(),
+            source_lines: log.source_lines.clone(),
+            modified: log.is_modified,
+            ambiguous_texts: log
+                .ambiguous_texts
+                .iter()
+                .map(AmbiguousTextReport::new)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct AmbiguousTextReport<'a> {
+    line: usize,
+    text: &'a str,
+    source_lines: &'a [usize],
+}
+impl<'a> AmbiguousTextReport<'a> {
+    fn new(a: &'a AmbiguousText) -> Self {
+        Self {
+            line: a.line,
+            text: &a.text,
+            source_lines: &a.source_lines,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorReport<'a> {
+    kind: String,
+    code: &'static str,
+    path: &'a Path,
+    line: Option<usize>,
+    message: &'a str,
+}
+impl<'a> ErrorReport<'a> {
+    fn new(e: &'a Error) -> Self {
+        Self {
+            kind: e.kind().to_string(),
+            code: e.code(),
+            path: e.rel_path(),
+            line: e.line(),
+            message: e.summary(),
+        }
+    }
+}
+
+/// Prints the report of `path` as a line of JSON, if it has `include_doc` regions or errors.
+pub fn print_file(path: &Path, status: Status, logs: &[LogEntry], errors: &[Error]) -> Result<()> {
+    if logs.is_empty() && errors.is_empty() {
+        return Ok(());
+    }
+    let report = FileReport {
+        path,
+        status,
+        includes: logs.iter().map(IncludeReport::new).collect(),
+        errors: errors.iter().map(ErrorReport::new).collect(),
+    };
+    println!("{}", serde_json::to_string(&report)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn include_report() {
+        let log = LogEntry {
+            source_path: PathBuf::from("/root/README.md"),
+            source_rel_path: PathBuf::from("README.md"),
+            source_range: 0..12,
+            source_lines: 1..3,
+            nested_source_paths: Vec::new(),
+            line: 5,
+            end_line: 8,
+            is_modified: true,
+            old_text: String::new(),
+            new_text: "/// # Title\n///\n/// text\n".into(),
+            old_end_attr: "// #[include_doc(\"README.md\", end)]".into(),
+            new_end_attr: "// #[include_doc(\"README.md\", end)]".into(),
+            ambiguous_texts: Vec::new(),
+        };
+        assert_eq!(
+            serde_json::to_string(&IncludeReport::new(&log)).unwrap(),
+            r#"{"source":"README.md","start_line":5,"end_line":8,"source_range":{"start":0,"end":12},"source_lines":{"start":1,"end":3},"modified":true}"#
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    sync::mpsc::channel,
+    time::Duration,
+};
+
+use crate::Engine;
+use anyhow::Result;
+use ignore::overrides::Override;
+use notify::{event::ModifyKind, EventKind, RecursiveMode, Watcher};
+use yansi::Paint;
+
+use super::{apply_file, walk, Opt, Stats};
+
+/// Time to wait for more events after a change, so that a burst of events is processed once.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Maps included files to the `*.rs` files that include them.
+#[derive(Default)]
+struct Index {
+    files: HashSet<PathBuf>,
+    /// The directories that are not ignored, to tell whether a new path is ignored.
+    dirs: HashSet<PathBuf>,
+    sources: HashMap<PathBuf, HashSet<PathBuf>>,
+    failed: HashSet<PathBuf>,
+}
+impl Index {
+    /// Walks `dirs` again to find the new `*.rs` files and directories.
+    ///
+    /// Returns the `*.rs` files that were not known.
+    fn scan(&mut self, dirs: &[PathBuf], filter: &Override) -> Result<Vec<PathBuf>> {
+        let mut new_files = Vec::new();
+        self.dirs = HashSet::new();
+        for e in walk(dirs, filter) {
+            let e = e?;
+            let Some(file_type) = e.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                self.dirs.insert(e.into_path());
+            } else if file_type.is_file() && is_rs(e.path()) && !self.files.contains(e.path()) {
+                self.files.insert(e.path().to_path_buf());
+                new_files.push(e.into_path());
+            }
+        }
+        Ok(new_files)
+    }
+
+    /// Returns `true` if a change to `path` may affect the `*.rs` files.
+    ///
+    /// Paths ignored by `.gitignore`, such as build output, are not relevant,
+    /// and neither are `*.rs` files ignored by `filter`.
+    fn is_relevant(&self, path: &Path, filter: &Override, source_roots: &[PathBuf]) -> bool {
+        if self.files.contains(path) || self.sources.contains_key(path) {
+            return true;
+        }
+        if is_rs(path) && filter.matched(path, false).is_ignore() {
+            return false;
+        }
+        let is_walked = path.parent().is_some_and(|dir| self.dirs.contains(dir));
+        is_walked || (!is_rs(path) && source_roots.iter().any(|r| path.starts_with(r)))
+    }
+
+    fn update(&mut self, path: &Path, sources: Option<Vec<PathBuf>>) {
+        self.remove(path);
+        self.files.insert(path.to_path_buf());
+        if let Some(sources) = sources {
+            for source in sources {
+                self.sources
+                    .entry(source)
+                    .or_default()
+                    .insert(path.to_path_buf());
+            }
+        } else {
+            self.failed.insert(path.to_path_buf());
+        }
+    }
+    fn remove(&mut self, path: &Path) {
+        self.files.remove(path);
+        self.failed.remove(path);
+        for files in self.sources.values_mut() {
+            files.remove(path);
+        }
+    }
+
+    /// Returns the `*.rs` files that should be applied again after `changed` paths are modified.
+    ///
+    /// Files that had errors are always included because the change may have fixed them.
+    fn affected(&self, changed: &HashSet<PathBuf>) -> BTreeSet<PathBuf> {
+        let mut files = BTreeSet::new();
+        for path in changed {
+            if self.files.contains(path) {
+                files.insert(path.clone());
+            }
+            if let Some(dependents) = self.sources.get(path) {
+                files.extend(dependents.iter().cloned());
+            }
+        }
+        if !files.is_empty() || changed.iter().any(|p| !is_rs(p)) {
+            files.extend(self.failed.iter().cloned());
+        }
+        files
+    }
+}
+
+fn is_rs(path: &Path) -> bool {
+    path.extension() == Some(OsStr::new("rs"))
+}
+
+pub fn watch(
+    engine: &Engine,
+    args: &Opt,
+    dirs: &[PathBuf],
+    filter: &Override,
+    source_roots: &[PathBuf],
+) -> Result<bool> {
+    let root = engine.root();
+    let source_roots = source_roots
+        .iter()
+        .map(|r| r.canonicalize())
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut index = Index::default();
+    let mut stats = Stats::default();
+    for path in index.scan(dirs, filter)? {
+        apply(engine, args, &path, &mut stats, &mut index);
+    }
+
+    let (tx, rx) = channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(root, RecursiveMode::Recursive)?;
+    for source_root in &source_roots {
+        watcher.watch(source_root, RecursiveMode::Recursive)?;
+    }
+    eprintln!("{}: {}", "watching".cyan().bold(), root.display());
+    loop {
+        let mut changed = HashSet::new();
+        add_changed(rx.recv()??, &mut changed);
+        while let Ok(event) = rx.recv_timeout(DEBOUNCE) {
+            add_changed(event?, &mut changed);
+        }
+        changed.retain(|p| index.is_relevant(p, filter, &source_roots));
+        let mut new_files = Vec::new();
+        if changed
+            .iter()
+            .any(|p| !index.files.contains(p) && (is_rs(p) || p.is_dir()))
+        {
+            // The paths in new directories were not known to be relevant, so new files are applied here.
+            match index.scan(dirs, filter) {
+                Ok(files) => new_files = files,
+                Err(e) => eprintln!("{}: {e}", "error".red().bold()),
+            }
+        }
+        let mut files = index.affected(&changed);
+        files.extend(new_files);
+        for path in files {
+            if !path.exists() {
+                index.remove(&path);
+                continue;
+            }
+            apply(engine, args, &path, &mut stats, &mut index);
+        }
+    }
+}
+
+/// Applies `path` and updates the index, reporting errors such as a failed write without stopping.
+fn apply(engine: &Engine, args: &Opt, path: &Path, stats: &mut Stats, index: &mut Index) {
+    match apply_file(engine, args, path, stats) {
+        Ok(sources) => index.update(path, sources),
+        Err(e) => {
+            eprintln!("{}: {e}", "error".red().bold());
+            index.update(path, None);
+        }
+    }
+}
+
+fn add_changed(event: notify::Event, changed: &mut HashSet<PathBuf>) {
+    match event.kind {
+        EventKind::Modify(ModifyKind::Metadata(_)) => return,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) => {}
+        _ => return,
+    }
+    changed.extend(event.paths);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn affected_by_source() {
+        let mut index = Index::default();
+        index.update(Path::new("/root/a.rs"), Some(vec!["/root/a.md".into()]));
+        index.update(Path::new("/root/b.rs"), Some(vec!["/root/b.md".into()]));
+        index.update(Path::new("/root/c.rs"), None);
+
+        let changed = HashSet::from([PathBuf::from("/root/a.md")]);
+        let files: Vec<_> = index.affected(&changed).into_iter().collect();
+        assert_eq!(
+            files,
+            vec![PathBuf::from("/root/a.rs"), PathBuf::from("/root/c.rs")]
+        );
+
+        let changed = HashSet::from([PathBuf::from("/root/b.rs")]);
+        let files: Vec<_> = index.affected(&changed).into_iter().collect();
+        assert_eq!(
+            files,
+            vec![PathBuf::from("/root/b.rs"), PathBuf::from("/root/c.rs")]
+        );
+    }
+    #[test]
+    fn relevant_paths() {
+        let mut index = Index::default();
+        index.dirs.insert("/root".into());
+        index.dirs.insert("/root/src".into());
+        index.update(Path::new("/root/src/a.rs"), Some(vec!["/docs/a.md".into()]));
+        let filter = Override::empty();
+        let source_roots = [PathBuf::from("/shared")];
+        let is_relevant = |path: &str| index.is_relevant(Path::new(path), &filter, &source_roots);
+        assert!(is_relevant("/root/src/a.rs"));
+        assert!(is_relevant("/docs/a.md"));
+        assert!(is_relevant("/root/src/b.md"));
+        assert!(is_relevant("/shared/c.md"));
+        assert!(!is_relevant("/root/target/debug/build.rs"));
+        assert!(!is_relevant("/shared/d.rs"));
+    }
+}
@@ -0,0 +1,139 @@
+use std::fmt::Write;
+use std::io::{stdout, IsTerminal};
+use std::path::Path;
+
+use yansi::{Condition, Paint};
+
+use crate::LogEntry;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<DiffLine<'a>> {
+    let n = old.len();
+    let m = new.len();
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if old[i] == new[j] {
+            lines.push(DiffLine::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            lines.push(DiffLine::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new[j]));
+            j += 1;
+        }
+    }
+    lines.extend(old[i..].iter().map(|l| DiffLine::Removed(l)));
+    lines.extend(new[j..].iter().map(|l| DiffLine::Added(l)));
+    lines
+}
+
+fn fmt_range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start - 1),
+        1 => format!("{start}"),
+        _ => format!("{start},{count}"),
+    }
+}
+
+/// Colors the diff only when stdout is a terminal, so that the diff can be piped to `patch`.
+const STDOUT_IS_TERMINAL: Condition = Condition::from(|| stdout().is_terminal());
+
+/// Formats modified regions as a unified diff with one hunk per region.
+///
+/// The end attribute is part of the hunk, so that a change to the recorded hash is shown.
+pub fn fmt_diff(rel_path: &Path, logs: &[LogEntry]) -> String {
+    let mut s = String::new();
+    let mut delta = 0isize;
+    for log in logs {
+        let old: Vec<_> = log
+            .old_text
+            .lines()
+            .chain([log.old_end_attr.as_str()])
+            .collect();
+        let new: Vec<_> = log
+            .new_text
+            .lines()
+            .chain([log.new_end_attr.as_str()])
+            .collect();
+        if log.is_modified && old != new {
+            if s.is_empty() {
+                let old_path = format!("--- a/{}", rel_path.display());
+                let new_path = format!("+++ b/{}", rel_path.display());
+                writeln!(&mut s, "{}", old_path.bold().whenever(STDOUT_IS_TERMINAL)).unwrap();
+                writeln!(&mut s, "{}", new_path.bold().whenever(STDOUT_IS_TERMINAL)).unwrap();
+            }
+            let old_start = log.line + 1;
+            let new_start = old_start.saturating_add_signed(delta);
+            let header = format!(
+                "@@ -{} +{} @@",
+                fmt_range(old_start, old.len()),
+                fmt_range(new_start, new.len())
+            );
+            writeln!(&mut s, "{}", header.cyan().whenever(STDOUT_IS_TERMINAL)).unwrap();
+            for line in diff_lines(&old, &new) {
+                match line {
+                    DiffLine::Same(l) => writeln!(&mut s, " {l}"),
+                    DiffLine::Removed(l) => {
+                        let l = format!("-{l}");
+                        writeln!(&mut s, "{}", l.red().whenever(STDOUT_IS_TERMINAL))
+                    }
+                    DiffLine::Added(l) => {
+                        let l = format!("+{l}");
+                        writeln!(&mut s, "{}", l.green().whenever(STDOUT_IS_TERMINAL))
+                    }
+                }
+                .unwrap();
+            }
+        }
+        delta += new.len() as isize - old.len() as isize;
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_lines_replace() {
+        assert_eq!(
+            diff_lines(&["a", "b", "c"], &["a", "x", "c", "d"]),
+            vec![
+                DiffLine::Same("a"),
+                DiffLine::Removed("b"),
+                DiffLine::Added("x"),
+                DiffLine::Same("c"),
+                DiffLine::Added("d"),
+            ]
+        );
+    }
+    #[test]
+    fn diff_lines_empty_old() {
+        assert_eq!(diff_lines(&[], &["a"]), vec![DiffLine::Added("a")]);
+    }
+    #[test]
+    fn range() {
+        assert_eq!(fmt_range(2, 0), "1,0");
+        assert_eq!(fmt_range(2, 1), "2");
+        assert_eq!(fmt_range(2, 3), "2,3");
+    }
+}
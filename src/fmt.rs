@@ -1,35 +1,254 @@
-use std::fmt::Display;
 use std::fmt::Write;
+use std::ops::Range;
 use std::path::Path;
 
 use yansi::Paint;
 
-pub fn fmt_link(rel_path: &Path, line: usize) -> String {
-    format!("--> {}:{}", rel_path.display(), line)
+use crate::text_pos::TextPos;
+
+/// A part of the input text to underline in a diagnostic.
+#[derive(Clone, Debug)]
+pub struct Label {
+    range: Range<usize>,
+    message: String,
+    primary: bool,
 }
-pub fn fmt_source<'a, L: Display>(lines: impl IntoIterator<Item = (L, &'a str)>) -> String {
-    let lines: Vec<_> = lines
-        .into_iter()
-        .map(|(line, content)| (line.to_string(), content))
+impl Label {
+    /// A label underlined with `^`, which is the location of the diagnostic.
+    pub fn primary(range: Range<usize>, message: impl Into<String>) -> Self {
+        Self {
+            range,
+            message: message.into(),
+            primary: true,
+        }
+    }
+    /// A label underlined with `-`, which gives context to the primary label.
+    pub fn secondary(range: Range<usize>, message: impl Into<String>) -> Self {
+        Self {
+            range,
+            message: message.into(),
+            primary: false,
+        }
+    }
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+    pub fn is_primary(&self) -> bool {
+        self.primary
+    }
+}
+
+/// Formats the lines of `input` with `labels` underlined, followed by `help`, in the style of rustc.
+///
+/// The location of the first primary label is shown as `--> {rel_path}:{line}:{column}`.
+pub fn fmt_snippet(rel_path: &Path, input: &str, labels: &[Label], help: Option<&str>) -> String {
+    let mut labels: Vec<_> = labels
+        .iter()
+        .map(|label| {
+            let pos = TextPos::from_str_offset(input, label.range.start);
+            (pos, label)
+        })
         .collect();
-    let max_width = lines.iter().map(|(line, _)| line.len()).max().unwrap_or(0);
+    let width = labels
+        .iter()
+        .map(|(pos, _)| pos.line.to_string().len())
+        .max()
+        .unwrap_or(1);
     let mut s = String::new();
-    let sep = "|".cyan().bold();
-    for (index, (line, content)) in lines.into_iter().enumerate() {
-        if index != 0 {
-            s.push('\n');
+    if let Some((pos, _)) = labels.iter().find(|(_, label)| label.primary) {
+        let arrow = "-->".cyan().bold();
+        writeln!(s, "{:width$}{arrow} {}:{}", "", rel_path.display(), pos).unwrap();
+    }
+    labels.sort_by_key(|(pos, label)| (pos.line, label.range.start));
+    let gutter = format!("{:width$} |", "").cyan().bold().to_string();
+    writeln!(s, "{gutter}").unwrap();
+    let mut last_line = None;
+    for (pos, label) in &labels {
+        let line_start = input[..label.range.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = input[line_start..]
+            .find('\n')
+            .map_or(input.len(), |i| line_start + i);
+        let content = input[line_start..line_end].trim_end_matches('\r');
+        if last_line != Some(pos.line) {
+            if last_line.is_some_and(|last| last + 1 < pos.line) {
+                writeln!(s, "{}", "...".cyan().bold()).unwrap();
+            }
+            let number = format!("{:>width$} |", pos.line).cyan().bold().to_string();
+            writeln!(s, "{number} {content}").unwrap();
+            last_line = Some(pos.line);
         }
-        s.push(' ');
-        if max_width != 0 {
-            for _ in line.len()..max_width {
-                s.push(' ');
+        let column = label.range.start - line_start;
+        let indent: String = content
+            .get(..column)
+            .unwrap_or(content)
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let len = input[label.range.start..label.range.end.clamp(label.range.start, line_end)]
+            .chars()
+            .count()
+            .max(1);
+        let mark = if label.primary { "^" } else { "-" }.repeat(len);
+        let mut line = format!("{mark} {}", label.message);
+        line.truncate(line.trim_end().len());
+        let line = if label.primary {
+            line.red().bold()
+        } else {
+            line.blue().bold()
+        };
+        writeln!(s, "{gutter} {indent}{line}").unwrap();
+    }
+    if let Some(help) = help {
+        writeln!(s, "{gutter}").unwrap();
+        write!(
+            s,
+            "{} {} {help}",
+            format!("{:width$} =", "").cyan().bold(),
+            "help:".bold()
+        )
+        .unwrap();
+    }
+    s.trim_end().to_string()
+}
+
+/// Returns the candidate most similar to `name`, if it is similar enough to be a typo.
+pub fn find_similar<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let max_distance = name.chars().count().max(3) / 3;
+    candidates
+        .into_iter()
+        .map(|c| (edit_distance(name, c), c))
+        .filter(|&(d, _)| d <= max_distance)
+        .min_by_key(|&(d, _)| d)
+        .map(|(_, c)| c)
+}
+
+/// Returns the edit distance between `a` and `b`, counting a transposition of adjacent characters as one edit.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
             }
-            s.push_str(&line);
-            s.push(' ');
         }
-        write!(&mut s, "{sep}").unwrap();
-        s.push(' ');
-        s.push_str(content);
     }
-    s
+    d[a.len()][b.len()]
+}
+
+/// Returns the ranges of the lines in `text` similar to `pattern`, the most similar first.
+///
+/// Whitespace around the lines is ignored, and at most `max` lines are returned.
+pub fn find_similar_lines(text: &str, pattern: &str, max: usize) -> Vec<Range<usize>> {
+    let pattern = pattern.lines().next().unwrap_or_default().trim();
+    if pattern.is_empty() {
+        return Vec::new();
+    }
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\r', '\n']);
+        let trimmed = content.trim();
+        let len = pattern.chars().count().max(trimmed.chars().count());
+        let distance = edit_distance(pattern, trimmed);
+        if !trimmed.is_empty() && distance * 2 < len {
+            let start = offset + content.len() - content.trim_start().len();
+            lines.push((distance, start..start + trimmed.len()));
+        }
+        offset += line.len();
+    }
+    lines.sort_by_key(|(distance, range)| (*distance, range.start));
+    lines
+        .into_iter()
+        .take(max)
+        .map(|(_, range)| range)
+        .collect()
+}
+
+/// Returns a help message suggesting the candidate similar to `name`, or listing all of `candidates`.
+pub fn fmt_suggestion(name: &str, candidates: &[&str]) -> String {
+    match find_similar(name, candidates.iter().copied()) {
+        Some(similar) => format!("did you mean `{similar}`?"),
+        None => format!("expected {}", fmt_one_of(candidates)),
+    }
+}
+
+/// Formats `items` as "`a`, `b` or `c`".
+pub fn fmt_one_of(items: &[&str]) -> String {
+    let items: Vec<_> = items.iter().map(|item| format!("`{item}`")).collect();
+    match items.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} or {last}", rest.join(", ")),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snippet() {
+        yansi::disable();
+        let input = "a\n// #[include_doc(\"a.md\", strat)]\nb\nc\n// end\n";
+        let start = input.find("strat").unwrap();
+        let end = input.find("end").unwrap();
+        assert_eq!(
+            fmt_snippet(
+                Path::new("lib.rs"),
+                input,
+                &[
+                    Label::primary(start..start + 5, "unknown action"),
+                    Label::secondary(end..end + 3, "end"),
+                ],
+                Some("did you mean `start`?"),
+            ),
+            r#" --> lib.rs:2:26
+  |
+2 | // #[include_doc("a.md", strat)]
+  |                          ^^^^^ unknown action
+...
+5 | // end
+  |    --- end
+  |
+  = help: did you mean `start`?"#
+        );
+    }
+    #[test]
+    fn similar() {
+        let actions = ["start", "end", "region", "section"];
+        assert_eq!(find_similar("strat", actions), Some("start"));
+        assert_eq!(find_similar("ned", actions), Some("end"));
+        assert_eq!(find_similar("regoin", actions), Some("region"));
+        assert_eq!(find_similar("unknown", actions), None);
+    }
+    #[test]
+    fn similar_lines() {
+        let text = "# Title\n\n## Installation\n\n## Usage\n  ## Install\r\n";
+        let lines: Vec<_> = find_similar_lines(text, "## Install", 3)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect();
+        assert_eq!(lines, ["## Install", "## Installation"]);
+        assert!(find_similar_lines(text, "Contribution", 3).is_empty());
+    }
+    #[test]
+    fn one_of() {
+        assert_eq!(fmt_one_of(&["a"]), "`a`");
+        assert_eq!(fmt_one_of(&["a", "b", "c"]), "`a`, `b` or `c`");
+    }
 }
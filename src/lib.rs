@@ -0,0 +1,1980 @@
+//! A utility for importing external Markdown files into `*.rs` file as doc comments.
+//!
+//! This crate exposes the engine used by the `rustdoc-include` command so that
+//! build scripts and other tools can run the same synchronization in-process.
+//!
+//! ```no_run
+//! use rustdoc_include::Engine;
+//! use std::path::Path;
+//!
+//! let engine = Engine::new("./");
+//! match engine.apply_file(Path::new("./src/lib.rs")) {
+//!     Ok(result) => {
+//!         if let Some(text) = result.text {
+//!             std::fs::write("./src/lib.rs", text)?;
+//!         }
+//!     }
+//!     Err(errors) => {
+//!         for e in errors {
+//!             eprintln!("{e}");
+//!         }
+//!     }
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+#![allow(clippy::result_large_err)]
+
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    fmt::Write,
+    fs::read,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use crate::fmt::*;
+use crate::text_pos::to_line;
+use anyhow::bail;
+use attr::{Attr, AttrOption, BadAttrError, Kind, OptionValue};
+use parse_display::Display;
+use template::Variables;
+use thiserror::Error;
+
+mod attr;
+/// The command line interface of the `rustdoc-include` and `cargo rustdoc-include` binaries.
+///
+/// This is not part of the public API, and is only available with the `cli` feature.
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod cli;
+mod diff;
+mod fmt;
+mod markdown;
+mod reverse;
+mod select;
+mod template;
+mod text_pos;
+
+pub use attr::DocStyle;
+pub use reverse::{reverse_str, ReverseResult, SourceUpdate};
+pub use select::SelectError;
+
+/// Synchronizes `include_doc` regions of `*.rs` files with the files they include.
+#[derive(Clone, Debug)]
+pub struct Engine {
+    root: PathBuf,
+    source_roots: Vec<PathBuf>,
+    force: bool,
+    record_hash: bool,
+    style: DocStyle,
+    line_ending: LineEnding,
+    heading_offset: isize,
+    defines: BTreeMap<String, String>,
+}
+impl Engine {
+    /// Creates an engine that can only include files under `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            source_roots: Vec::new(),
+            force: false,
+            record_hash: false,
+            style: DocStyle::Comment,
+            line_ending: LineEnding::Auto,
+            heading_offset: 0,
+            defines: BTreeMap::new(),
+        }
+    }
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Allow including files under `roots` in addition to the root.
+    pub fn source_roots(mut self, roots: Vec<PathBuf>) -> Self {
+        self.source_roots = roots;
+        self
+    }
+
+    /// Overwrite regions edited by hand instead of reporting [`ApplyError::Edited`].
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Record the hash of the generated text in every end attribute, not only in those that already have one.
+    ///
+    /// The hash is used to detect regions edited by hand.
+    pub fn record_hash(mut self, record_hash: bool) -> Self {
+        self.record_hash = record_hash;
+        self
+    }
+
+    /// The style of the generated documentation for start attributes without `style("...")`.
+    pub fn style(mut self, style: DocStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
+
+    /// The number of levels to add to the headings in the included text,
+    /// for start attributes without `heading_offset({n})`, `code` or `hide`.
+    pub fn heading_offset(mut self, offset: isize) -> Self {
+        self.heading_offset = offset;
+        self
+    }
+
+    /// Defines the value of `{{name}}` placeholders in the included text,
+    /// taking precedence over `{{crate.*}}` and `{{env.*}}`.
+    pub fn define(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.defines.insert(name.into(), value.into());
+        self
+    }
+
+    /// Applies `include_doc` regions in `input`, resolving include paths relative to `base`.
+    ///
+    /// All errors in `input` are reported, not only the first one.
+    pub fn apply_str<'a>(
+        &self,
+        base: &Path,
+        input: &'a str,
+    ) -> Result<ApplyResult, Vec<ApplyError<'a>>> {
+        let (regions, errors) = self.regions(base, input);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(apply_regions(input, regions))
+    }
+
+    /// Applies each `include_doc` region in `input` separately, so that errors in some regions
+    /// do not prevent the others from being applied.
+    ///
+    /// The text of `input` is not modified. Use [`Region::new_text`] to update each region.
+    pub fn regions<'a>(&self, base: &Path, input: &'a str) -> (Vec<Region>, Vec<ApplyError<'a>>) {
+        let mut regions = Vec::new();
+        let mut errors = Vec::new();
+        let eol = self.eol(input);
+        let vars = self.variables(base);
+        for (start, end) in find_pairs(input, &mut errors) {
+            match self.apply_pair(base, input, &start, &end, eol, &vars) {
+                Ok(log) => {
+                    let new_text = format!(
+                        "{}{eol}{}{}",
+                        &input[start.range()],
+                        log.new_text,
+                        log.new_end_attr
+                    );
+                    regions.push(Region {
+                        range: start.range.start..end.range.end,
+                        new_text,
+                        log,
+                    });
+                }
+                Err(e) => errors.push(e),
+            }
+        }
+        errors.sort_by_key(|e| e.offset());
+        (regions, errors)
+    }
+
+    fn apply_pair<'a>(
+        &self,
+        base: &Path,
+        input: &str,
+        start: &Attr<'a>,
+        end: &Attr<'a>,
+        eol: &str,
+        vars: &Variables,
+    ) -> Result<LogEntry, ApplyError<'a>> {
+        let s = self
+            .include(base, start.path)
+            .map_err(|e| e.into_apply_error(start.clone()))?;
+        let mut ambiguous = Vec::new();
+        let source_range = trim_range(&s, start, end, &mut ambiguous)?;
+        let text = substitute(&s, source_range.clone(), start, vars)?;
+        let text = self.transform(&text, start);
+        let style = self.doc_style(start);
+        if style == DocStyle::Block && (text.contains("*/") || text.contains("/*")) {
+            return Err(ApplyError::BlockComment(start.clone()));
+        }
+        let new_text = to_doc_comment(&text, start.kind, style, start.indent(input), eol);
+        let old_text = region_text(input, start, end);
+        let mut is_modified = is_modified(&new_text, input, start, end);
+        if let Some(hash) = end.hash() {
+            if is_modified && !self.force && text_hash(old_text) != hash {
+                return Err(ApplyError::Edited {
+                    start: start.clone(),
+                    end: end.clone(),
+                });
+            }
+        }
+        let mut end_attr = None;
+        if self.record_hash || end.hash().is_some() {
+            let hash = text_hash(&new_text);
+            if end.hash() != Some(&hash) {
+                end_attr = Some(end_attr_with_hash(input, end, &hash));
+                is_modified = true;
+            }
+        }
+        let source_range = s.to_top_level_range(source_range);
+        let source_text = &s.files[0].text;
+        let source_lines = to_line(source_text, source_range.start)
+            ..to_line(source_text, source_range.end) + usize::from(!source_range.is_empty());
+        let ambiguous_texts = ambiguous
+            .into_iter()
+            .map(|(attr, matches)| AmbiguousText {
+                line: attr.line(input),
+                text: match attr.arg {
+                    attr::ActionArg::Text(text) => text.to_string(),
+                    _ => String::new(),
+                },
+                source_lines: matches
+                    .into_iter()
+                    .map(|range| to_line(source_text, s.to_top_level_range(range).start))
+                    .collect(),
+            })
+            .collect();
+        let log = LogEntry {
+            source_path: s.path,
+            source_rel_path: s.rel_path,
+            nested_source_paths: s.nested_paths,
+            source_range,
+            source_lines,
+            line: start.line(input),
+            end_line: end.line(input),
+            is_modified,
+            old_text: old_text.to_string(),
+            new_text,
+            old_end_attr: input[end.range()].to_string(),
+            new_end_attr: end_attr.unwrap_or_else(|| input[end.range()].to_string()),
+            ambiguous_texts,
+        };
+        Ok(log)
+    }
+
+    /// Reads `path` and applies `include_doc` regions in it.
+    ///
+    /// The file is not written; the updated text is returned in [`ApplyResult::text`].
+    pub fn apply_file(&self, path: &Path) -> Result<ApplyResult, Vec<Error>> {
+        self.apply_file_with_logs(path).map_err(|e| e.errors)
+    }
+
+    /// Reads `path` and applies `include_doc` regions in it, like [`Engine::apply_file`].
+    ///
+    /// On failure, the logs of the regions without errors are returned along with the errors.
+    pub fn apply_file_with_logs(&self, path: &Path) -> Result<ApplyResult, FileErrors> {
+        let rel_path = self.rel_path(path);
+        let input = self.read_file(path).map_err(|errors| FileErrors {
+            errors,
+            logs: Vec::new(),
+        })?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        let (regions, errors) = self.regions(base, &input);
+        if !errors.is_empty() {
+            return Err(FileErrors {
+                errors: to_errors(&errors, rel_path, &input),
+                logs: regions.into_iter().map(|region| region.log).collect(),
+            });
+        }
+        Ok(apply_regions(&input, regions))
+    }
+
+    /// Reads `path` and returns the included files updated with the edits made to its doc comments.
+    ///
+    /// See [`Engine::reverse_str`] for how edited regions are detected.
+    pub fn reverse_file(&self, path: &Path) -> Result<ReverseResult, Vec<Error>> {
+        let rel_path = self.rel_path(path);
+        let input = self.read_file(path)?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        self.reverse_str(base, &input)
+            .map_err(|errors| to_errors(&errors, rel_path, &input))
+    }
+
+    fn read_file(&self, path: &Path) -> Result<String, Vec<Error>> {
+        let rel_path = self.rel_path(path);
+        read(path)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| String::from_utf8(bytes).map_err(|e| e.to_string()))
+            .map_err(|reason| {
+                vec![Error {
+                    kind: ErrorKind::Read,
+                    rel_path: rel_path.to_path_buf(),
+                    line: None,
+                    message: format!("cannot read `{}` ({})", rel_path.display(), reason),
+                }]
+            })
+    }
+
+    /// Returns `path` relative to the root, or `path` itself if it is outside of the root.
+    pub fn rel_path<'p>(&self, path: &'p Path) -> &'p Path {
+        path.strip_prefix(&self.root).unwrap_or(path)
+    }
+
+    fn eol<'s>(&self, input: &'s str) -> &'s str {
+        match self.line_ending {
+            LineEnding::Auto => detect_eol(input),
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+    fn doc_style(&self, start: &Attr) -> DocStyle {
+        start.doc_style().unwrap_or(self.style)
+    }
+    /// Returns the number of levels to add to the headings.
+    ///
+    /// Headings are not shifted with `hide`, because the hidden lines start with `# ` as well.
+    fn heading_offset_of(&self, start: &Attr) -> Option<isize> {
+        if start.hide().is_some() {
+            return None;
+        }
+        start.heading_offset().or_else(|| {
+            Some(self.heading_offset).filter(|&offset| offset != 0 && start.code().is_none())
+        })
+    }
+
+    fn variables<'e>(&'e self, base: &'e Path) -> Variables<'e> {
+        Variables::new(&self.defines, base)
+    }
+
+    /// Applies the transformations specified by the options of the start attribute.
+    fn transform<'t>(&self, text: &'t str, start: &Attr) -> Cow<'t, str> {
+        let mut text = Cow::Borrowed(text);
+        if let attr::ActionArg::Item(_) | attr::ActionArg::Anchor(_) = start.arg {
+            text = Cow::Owned(select::dedent(&select::remove_anchor_lines(&text)));
+        }
+        if let Some(pattern) = start.hide() {
+            text = Cow::Owned(select::hide_lines(&text, pattern.as_ref()));
+        }
+        if let Some(offset) = self.heading_offset_of(start) {
+            text = Cow::Owned(markdown::shift_headings(&text, offset));
+        }
+        if let Some(info_string) = start.code() {
+            text = Cow::Owned(markdown::to_code_block(&text, info_string));
+        }
+        text
+    }
+    /// Returns `true` if the included text is transformed, so that it cannot be written back.
+    fn is_transformed(&self, start: &Attr) -> bool {
+        matches!(
+            start.arg,
+            attr::ActionArg::Item(_) | attr::ActionArg::Anchor(_)
+        ) || self.heading_offset_of(start).is_some()
+            || start.hide().is_some()
+            || start.code().is_some()
+    }
+
+    /// Reads `source` relative to `base` and expands the `<!-- include: {path} -->` lines in it.
+    fn include(&self, base: &Path, source: &str) -> Result<IncludeResult, IncludeError> {
+        let (path, file) = self
+            .read_source(base, source)
+            .map_err(|e| IncludeError::Read(e.to_string()))?;
+        let rel_path = file.rel_path.clone();
+        let mut files = vec![file];
+        let mut chain = vec![(path.clone(), 0)];
+        let mut nested_paths = Vec::new();
+        let (text, segments) = self.expand(&mut files, &mut chain, None, &mut nested_paths)?;
+        Ok(IncludeResult {
+            path,
+            rel_path,
+            text,
+            nested_paths,
+            files,
+            segments,
+        })
+    }
+
+    /// Replaces the `<!-- include: {path} -->` lines in the last file of `chain` with the expanded text of the files.
+    ///
+    /// `chain` is the files being expanded with their indexes in `files`.
+    /// `top` is the range of the include line in the top-level file, if the file is included by another one.
+    fn expand(
+        &self,
+        files: &mut Vec<SourceText>,
+        chain: &mut Vec<(PathBuf, usize)>,
+        top: Option<Range<usize>>,
+        nested_paths: &mut Vec<PathBuf>,
+    ) -> Result<(String, Vec<Segment>), IncludeError> {
+        let (path, file) = chain.last().unwrap().clone();
+        let base = path.parent().unwrap_or(Path::new(""));
+        let text = files[file].text.clone();
+        let mut s = String::new();
+        let mut segments = Vec::new();
+        let push = |s: &mut String, segments: &mut Vec<Segment>, range: Range<usize>| {
+            if !range.is_empty() {
+                segments.push(Segment {
+                    range: s.len()..s.len() + range.len(),
+                    file,
+                    offset: range.start,
+                    top: top.clone().unwrap_or(range.clone()),
+                });
+                s.push_str(&text[range]);
+            }
+        };
+        let mut last = 0;
+        for d in markdown::include_directives(&text) {
+            let (nested_path, nested) = self.read_source(base, d.path).map_err(|e| {
+                IncludeError::Read(format!(
+                    "cannot read `{}` included from `{}` ({e})",
+                    d.path,
+                    files[file].rel_path.display()
+                ))
+            })?;
+            if chain.iter().any(|(p, _)| *p == nested_path) {
+                let mut paths: Vec<_> = chain
+                    .iter()
+                    .map(|&(_, i)| files[i].rel_path.clone())
+                    .collect();
+                paths.push(nested.rel_path);
+                return Err(IncludeError::Cycle(paths));
+            }
+            push(&mut s, &mut segments, last..d.range.start);
+            files.push(nested);
+            chain.push((nested_path.clone(), files.len() - 1));
+            let top = top.clone().unwrap_or(d.range.clone());
+            let (expanded, nested_segments) = self.expand(files, chain, Some(top), nested_paths)?;
+            chain.pop();
+            nested_paths.push(nested_path);
+            let len = expanded.trim_end_matches(['\r', '\n']).len();
+            for mut segment in nested_segments {
+                if segment.range.start < len {
+                    segment.range =
+                        s.len() + segment.range.start..s.len() + segment.range.end.min(len);
+                    segments.push(segment);
+                }
+            }
+            s.push_str(&expanded[..len]);
+            last = d.range.end;
+        }
+        push(&mut s, &mut segments, last..text.len());
+        Ok((s, segments))
+    }
+
+    /// Reads `source` relative to `base`, which must be inside the root or one of the source roots.
+    fn read_source(&self, base: &Path, source: &str) -> anyhow::Result<(PathBuf, SourceText)> {
+        let path = base.join(source).canonicalize()?;
+        let roots = self
+            .source_roots
+            .iter()
+            .filter_map(|r| r.canonicalize().ok());
+        for root in std::iter::once(self.root.canonicalize()?).chain(roots) {
+            if let Ok(rel_path) = path.strip_prefix(&root) {
+                let file = SourceText {
+                    rel_path: rel_path.to_path_buf(),
+                    text: String::from_utf8(read(&path)?)?,
+                };
+                return Ok((path, file));
+            }
+        }
+        bail!("source is out of root");
+    }
+}
+
+/// The line ending of generated doc comments.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+#[non_exhaustive]
+pub enum LineEnding {
+    /// The line ending of the `*.rs` file.
+    #[default]
+    Auto,
+    Lf,
+    CrLf,
+}
+impl LineEnding {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "auto" => Some(LineEnding::Auto),
+            "lf" => Some(LineEnding::Lf),
+            "crlf" => Some(LineEnding::CrLf),
+            _ => None,
+        }
+    }
+}
+
+/// Replaces the regions in `input` with their new text.
+fn apply_regions(input: &str, regions: Vec<Region>) -> ApplyResult {
+    let mut logs = Vec::new();
+    let mut text = String::new();
+    let mut text_is_modified = false;
+    let mut last_offset = 0;
+    for region in regions {
+        text.push_str(&input[last_offset..region.range.start]);
+        text.push_str(&region.new_text);
+        last_offset = region.range.end;
+        text_is_modified |= region.log.is_modified;
+        logs.push(region.log);
+    }
+    text.push_str(&input[last_offset..]);
+    let text = if text_is_modified { Some(text) } else { None };
+    ApplyResult { text, logs }
+}
+fn to_errors(errors: &[ApplyError], rel_path: &Path, input: &str) -> Vec<Error> {
+    errors.iter().map(|e| e.to_error(rel_path, input)).collect()
+}
+
+/// Applies `include_doc` regions in `input`.
+///
+/// Include paths are resolved relative to `base` and must be inside `root`.
+/// All errors in `input` are reported, not only the first one.
+pub fn apply_str<'a>(
+    root: &Path,
+    base: &Path,
+    input: &'a str,
+) -> Result<ApplyResult, Vec<ApplyError<'a>>> {
+    Engine::new(root).apply_str(base, input)
+}
+
+/// The location of a valid `include_doc` attribute, for editors.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct AttrLocation<'a> {
+    /// The range of the line of the attribute, without the line ending.
+    pub range: Range<usize>,
+    /// The path of the included file, as written in the attribute.
+    pub path: &'a str,
+    /// The range of the path, including the quotes.
+    pub path_range: Range<usize>,
+}
+
+/// Returns the locations of the valid `include_doc` attributes in `input`.
+pub fn attr_locations(input: &str) -> Vec<AttrLocation<'_>> {
+    Attr::find_iter(input)
+        .flatten()
+        .map(|attr| AttrLocation {
+            path_range: attr.spans(input).path,
+            range: attr.range,
+            path: attr.path,
+        })
+        .collect()
+}
+
+/// Returns pairs of start and end attributes, and adds errors for unpaired attributes to `errors`.
+fn find_pairs<'a>(input: &'a str, errors: &mut Vec<ApplyError<'a>>) -> Vec<(Attr<'a>, Attr<'a>)> {
+    let mut pairs = Vec::new();
+    let mut attr_start = None;
+    for attr in Attr::find_iter(input) {
+        match make_pair(&mut attr_start, attr) {
+            Ok(Some(pair)) => pairs.push(pair),
+            Ok(None) => {}
+            Err(e) => errors.push(e),
+        }
+    }
+    if let Some(start) = attr_start {
+        errors.push(ApplyError::MissingAttr(start));
+    }
+    pairs
+}
+fn make_pair<'a>(
+    start: &mut Option<Attr<'a>>,
+    attr: Result<Attr<'a>, BadAttrError>,
+) -> Result<Option<(Attr<'a>, Attr<'a>)>, ApplyError<'a>> {
+    match attr {
+        Ok(attr) => {
+            if attr.action == attr::Action::Start {
+                if let Some(start) = start.replace(attr) {
+                    Err(ApplyError::MissingAttr(start))
+                } else {
+                    Ok(None)
+                }
+            } else {
+                let end = attr;
+                if let Some(start) = start.take() {
+                    if let Some(mismatch) = start.mismatch(&end) {
+                        Err(ApplyError::MismatchAttr {
+                            start,
+                            end,
+                            mismatch,
+                        })
+                    } else {
+                        Ok(Some((start, end)))
+                    }
+                } else {
+                    Err(ApplyError::MissingAttr(end))
+                }
+            }
+        }
+        Err(e) => Err(ApplyError::BadAttr(e)),
+    }
+}
+/// Returns the range of the text to include.
+///
+/// The texts of `start("...")` and `end("...")` that match more than once are added to `ambiguous`
+/// with the ranges of their matches.
+fn trim_range<'b>(
+    s: &IncludeResult,
+    start: &Attr<'b>,
+    end: &Attr<'b>,
+    ambiguous: &mut Vec<(Attr<'b>, Vec<Range<usize>>)>,
+) -> Result<Range<usize>, ApplyError<'b>> {
+    let text = &s.text;
+    let select_error = |error| ApplyError::Select {
+        attr: start.clone(),
+        error,
+    };
+    let selected = match start.arg {
+        attr::ActionArg::Region(name) => select::region(text, name).map_err(select_error)?,
+        attr::ActionArg::Section(path) => {
+            let include_heading = start.option("exclude_heading").is_none();
+            select::section(text, path, include_heading).map_err(select_error)?
+        }
+        attr::ActionArg::Item(signature) => select::item(text, signature).map_err(select_error)?,
+        attr::ActionArg::Anchor(name) => select::anchor(text, name).map_err(select_error)?,
+        _ => 0..text.len(),
+    };
+    let offset = selected.start;
+    let text = &text[selected];
+    let index_start = match start.arg {
+        attr::ActionArg::Line(line) => line_offset(text, line),
+        attr::ActionArg::LineRev(line) => line_offset_rev(text, line),
+        attr::ActionArg::Text(p) => {
+            let matches = find_text(s, offset, p, start)?;
+            let first = matches[0].start;
+            if matches.len() > 1 {
+                ambiguous.push((start.clone(), matches));
+            }
+            first - offset
+        }
+        _ => 0,
+    };
+    let index_end = match end.arg {
+        attr::ActionArg::Line(line) => line_offset(text, line),
+        attr::ActionArg::LineRev(line) => line_offset_rev(text, line),
+        attr::ActionArg::Text(p) => {
+            // Searched after the start text, which may contain the end text, such as "```rust".
+            let after = match start.arg {
+                attr::ActionArg::Text(start_text) => index_start + start_text.len(),
+                _ => index_start,
+            };
+            let matches = find_text(s, offset + after, p, end)?;
+            let first = matches[0].start;
+            if matches.len() > 1 {
+                ambiguous.push((end.clone(), matches));
+            }
+            first - offset
+        }
+        _ => text.len(),
+    };
+    let index_end = index_end.max(index_start);
+    // Selected parts and code keep the indentation of their first line, which is significant.
+    let index_start = if start.arg.is_selector() || start.code().is_some() {
+        index_end - trim_start_lines(&text[index_start..index_end]).len()
+    } else {
+        index_end - text[index_start..index_end].trim_start().len()
+    };
+    let index_end = index_start + text[index_start..index_end].trim_end().len();
+    Ok(offset + index_start..offset + index_end)
+}
+/// Replaces the `{{name}}` placeholders in the text of `s` in `range`.
+fn substitute<'t, 'b>(
+    s: &'t IncludeResult,
+    range: Range<usize>,
+    start: &Attr<'b>,
+    vars: &Variables,
+) -> Result<Cow<'t, str>, ApplyError<'b>> {
+    template::substitute(&s.text[range.clone()], vars).map_err(|e| {
+        let range = range.start + e.range.start..range.start + e.range.end;
+        let (source, ranges) = s.to_source_text(vec![range]);
+        ApplyError::UndefinedVariable {
+            attr: start.clone(),
+            source,
+            similar: vars.find_similar(&e.name),
+            name: e.name,
+            range: ranges[0].clone(),
+        }
+    })
+}
+/// Returns the ranges of `p` in the text of `s` after `from`, or an error with the similar lines if there are none.
+fn find_text<'b>(
+    s: &IncludeResult,
+    from: usize,
+    p: &str,
+    attr: &Attr<'b>,
+) -> Result<Vec<Range<usize>>, ApplyError<'b>> {
+    let matches: Vec<_> = s.text[from..]
+        .match_indices(p)
+        .map(|(index, _)| from + index..from + index + p.len())
+        .collect();
+    if matches.is_empty() {
+        let similar = find_similar_lines(&s.text[from..], p, 3)
+            .into_iter()
+            .map(|r| from + r.start..from + r.end)
+            .collect();
+        let (source, similar) = s.to_source_text(similar);
+        return Err(ApplyError::TextNotFound {
+            attr: attr.clone(),
+            similar,
+            source,
+        });
+    }
+    Ok(matches)
+}
+/// Removes the leading lines that contain only whitespace.
+fn trim_start_lines(mut text: &str) -> &str {
+    while let Some(i) = text.find('\n') {
+        if !text[..i].trim().is_empty() {
+            return text;
+        }
+        text = &text[i + 1..];
+    }
+    if text.trim().is_empty() {
+        ""
+    } else {
+        text
+    }
+}
+fn line_offset(text: &str, mut line: usize) -> usize {
+    if line <= 1 {
+        return 0;
+    }
+    line -= 1;
+    for (index, c) in text.char_indices() {
+        if c == '\n' {
+            line -= 1;
+            if line == 0 {
+                return index + 1;
+            }
+        }
+    }
+    text.len()
+}
+fn line_offset_rev(text: &str, mut line: usize) -> usize {
+    if line == 0 {
+        return text.len();
+    }
+    for (index, c) in text.char_indices().rev() {
+        if c == '\n' {
+            line -= 1;
+            if line == 0 {
+                return index;
+            }
+        }
+    }
+    0
+}
+fn region_text<'a>(text_old: &'a str, start: &Attr, end: &Attr) -> &'a str {
+    &text_old[region_range(text_old, start, end)]
+}
+/// Returns the range between the start and end attributes, excluding the line break after the start attribute.
+fn region_range(text_old: &str, start: &Attr, end: &Attr) -> Range<usize> {
+    let old_text = &text_old[start.range.end..end.range.start];
+    let eol_len = if old_text.starts_with('\n') {
+        1
+    } else if old_text.starts_with("\r\n") {
+        2
+    } else {
+        0
+    };
+    start.range.end + eol_len..end.range.start
+}
+/// Returns the source of `end` with its `hash(...)` option set to `hash`, indented as in `input`.
+fn end_attr_with_hash(input: &str, end: &Attr, hash: &str) -> String {
+    let mut attr = end.clone();
+    attr.options.retain(|o| o.name != "hash");
+    attr.options.push(AttrOption {
+        name: "hash",
+        value: OptionValue::Text(hash),
+    });
+    format!("{}{}", end.indent(input), attr.to_source())
+}
+fn is_modified(text_new: &str, text_old: &str, start: &Attr, end: &Attr) -> bool {
+    let old_text = &text_old[start.range.end..end.range.start];
+    if old_text.starts_with('\n') {
+        text_new != &old_text[1..]
+    } else if old_text.starts_with("\r\n") {
+        text_new != &old_text[2..]
+    } else {
+        true
+    }
+}
+
+/// Returns the FNV-1a hash of `s` with line breaks normalized, as a hexadecimal string.
+fn text_hash(s: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in s.replace("\r\n", "\n").bytes() {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{hash:016x}")
+}
+
+fn detect_eol(s: &str) -> &str {
+    if let Some(i) = s.find('\n') {
+        if let Some(ch) = s[..i].chars().last() {
+            if ch == '\r' {
+                return "\r\n";
+            }
+        }
+    }
+    "\n"
+}
+
+struct IncludeResult {
+    path: PathBuf,
+    rel_path: PathBuf,
+    /// The text of the file with the `<!-- include: {path} -->` lines expanded.
+    text: String,
+    /// The canonical paths of the files included with `<!-- include: {path} -->`, directly or indirectly.
+    nested_paths: Vec<PathBuf>,
+    /// The files `text` is made of, the included file first.
+    files: Vec<SourceText>,
+    /// The parts of `text` copied from `files`, in order.
+    segments: Vec<Segment>,
+}
+
+/// A part of the expanded text copied from one of the files.
+struct Segment {
+    /// The range in the expanded text.
+    range: Range<usize>,
+    /// The index of the file in [`IncludeResult::files`].
+    file: usize,
+    /// The offset of the part in the file.
+    offset: usize,
+    /// The range in the top-level file, which is the include line for parts of nested files.
+    top: Range<usize>,
+}
+
+enum IncludeError {
+    Read(String),
+    /// The relative paths of the chain of includes, ending with the file included again.
+    Cycle(Vec<PathBuf>),
+}
+impl IncludeError {
+    fn into_apply_error(self, attr: Attr) -> ApplyError {
+        match self {
+            IncludeError::Read(reason) => ApplyError::SourceRead { attr, reason },
+            IncludeError::Cycle(chain) => ApplyError::IncludeCycle { attr, chain },
+        }
+    }
+}
+impl IncludeResult {
+    /// Returns the segment containing `offset`, or ending at it if it is at the end of the text.
+    fn segment_at(&self, offset: usize) -> Option<&Segment> {
+        self.segments
+            .iter()
+            .find(|seg| seg.range.contains(&offset))
+            .or_else(|| self.segments.iter().rfind(|seg| seg.range.end == offset))
+    }
+
+    /// Returns the file containing the first of `ranges` of the expanded text,
+    /// with the ranges in that file. Ranges in other files are omitted.
+    fn to_source_text(&self, ranges: Vec<Range<usize>>) -> (SourceText, Vec<Range<usize>>) {
+        let Some(first) = ranges.first().and_then(|r| self.segment_at(r.start)) else {
+            return (self.files[0].clone(), Vec::new());
+        };
+        let file = first.file;
+        let ranges = ranges
+            .into_iter()
+            .filter_map(|range| {
+                let seg = self.segment_at(range.start)?;
+                let start = seg.offset + range.start - seg.range.start;
+                let end = seg.offset + range.end.min(seg.range.end) - seg.range.start;
+                (seg.file == file).then_some(start..end)
+            })
+            .collect();
+        (self.files[file].clone(), ranges)
+    }
+
+    /// Returns `range` of the expanded text in the top-level file,
+    /// where the text of nested files is at their `<!-- include: {path} -->` lines.
+    fn to_top_level_range(&self, range: Range<usize>) -> Range<usize> {
+        let map = |offset: usize, is_end: bool| {
+            let seg = if is_end && offset > 0 {
+                self.segment_at(offset - 1)
+            } else {
+                self.segment_at(offset)
+            };
+            match seg {
+                Some(seg) if seg.file == 0 => seg.offset + offset - seg.range.start,
+                Some(seg) if is_end => seg.top.end,
+                Some(seg) => seg.top.start,
+                None => 0,
+            }
+        };
+        let start = map(range.start, false);
+        start..map(range.end, true).max(start)
+    }
+}
+
+/// The text of an included file, kept in errors to show the location in it.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct SourceText {
+    pub rel_path: PathBuf,
+    pub text: String,
+}
+
+/// Formats `s` as documentation in `style`, with each line prefixed with `indent`.
+///
+/// The lines inside a raw string or a block comment are not indented, because the indentation would be part of the text.
+fn to_doc_comment(s: &str, kind: Kind, style: DocStyle, indent: &str, eol: &str) -> String {
+    let mut r = String::new();
+    let mut buf = String::new();
+    match style {
+        DocStyle::Comment => {
+            for line in s.lines() {
+                buf.clear();
+                buf.push_str(kind.doc_comment_prefix());
+                buf.push_str(line);
+                r.push_str(indent);
+                r.push_str(buf.trim_end());
+                r.push_str(eol);
+            }
+        }
+        DocStyle::Attr => {
+            for line in s.lines() {
+                let line = line.trim_end();
+                let space = if line.is_empty() { "" } else { " " };
+                let line = line.replace('\\', "\\\\").replace('"', "\\\"");
+                let bang = kind.attr_bang();
+                write!(r, "{indent}#{bang}[doc = \"{space}{line}\"]{eol}").unwrap();
+            }
+        }
+        DocStyle::RawAttr => {
+            let mut hashes = String::new();
+            while s.contains(&format!("\"{hashes}")) {
+                hashes.push('#');
+            }
+            let bang = kind.attr_bang();
+            write!(r, "{indent}#{bang}[doc = r{hashes}\"{eol}").unwrap();
+            for line in s.lines() {
+                r.push_str(line.trim_end());
+                r.push_str(eol);
+            }
+            write!(r, "{indent}\"{hashes}]{eol}").unwrap();
+        }
+        DocStyle::Block => {
+            r.push_str(indent);
+            r.push_str(kind.block_doc_comment_start());
+            r.push_str(eol);
+            for line in s.lines() {
+                r.push_str(line.trim_end());
+                r.push_str(eol);
+            }
+            r.push_str(indent);
+            r.push_str("*/");
+            r.push_str(eol);
+        }
+    }
+    r
+}
+
+/// An `include_doc` region applied by [`Engine::regions`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Region {
+    /// The byte range from the start of the start attribute to the end of the end attribute.
+    pub range: Range<usize>,
+    /// The text to replace `range` with, including the attributes.
+    pub new_text: String,
+    pub log: LogEntry,
+}
+
+/// The result of applying `include_doc` regions to a `*.rs` file.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct ApplyResult {
+    /// The updated text, or `None` if no region was modified.
+    pub text: Option<String>,
+    /// One entry per `include_doc` region, in source order.
+    pub logs: Vec<LogEntry>,
+}
+
+/// The errors of a `*.rs` file, returned by [`Engine::apply_file_with_logs`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct FileErrors {
+    pub errors: Vec<Error>,
+    /// One entry per `include_doc` region without errors, in source order.
+    pub logs: Vec<LogEntry>,
+}
+
+impl ApplyResult {
+    /// Returns a unified diff of the modified regions.
+    pub fn to_diff(&self, rel_path: &Path) -> String {
+        diff::fmt_diff(rel_path, &self.logs)
+    }
+}
+
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct LogEntry {
+    /// The canonical path of the included file.
+    pub source_path: PathBuf,
+    /// The path of the included file, relative to the root or the source root that contains it.
+    pub source_rel_path: PathBuf,
+    /// The canonical paths of the files included by the included file with `<!-- include: {path} -->`,
+    /// directly or indirectly.
+    pub nested_source_paths: Vec<PathBuf>,
+    /// The byte range of the included text in the included file, before the transformations.
+    ///
+    /// Text from nested included files is located at their `<!-- include: {path} -->` lines.
+    pub source_range: Range<usize>,
+    /// The lines of `source_range`. The end is exclusive.
+    pub source_lines: Range<usize>,
+    /// The line number of the start attribute.
+    pub line: usize,
+    /// The line number of the end attribute.
+    pub end_line: usize,
+    pub is_modified: bool,
+    /// The text between the start and end attributes before applying.
+    pub old_text: String,
+    /// The text between the start and end attributes after applying.
+    pub new_text: String,
+    /// The line of the end attribute before applying, without the line ending.
+    pub old_end_attr: String,
+    /// The line of the end attribute after applying, which differs if its hash was updated.
+    pub new_end_attr: String,
+    /// The texts of `start("...")` and `end("...")` that match more than once in the included file.
+    pub ambiguous_texts: Vec<AmbiguousText>,
+}
+
+/// A text of `start("...")` or `end("...")` that matches more than once in the included file.
+///
+/// This is not an error; the first match is used, but the region may not end where it was meant to.
+/// The text of `end("...")` is searched after the start of the region.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct AmbiguousText {
+    /// The line number of the attribute.
+    pub line: usize,
+    pub text: String,
+    /// The line numbers of the matches in the included file. The first one is used.
+    pub source_lines: Vec<usize>,
+}
+
+/// An error that occurred while applying `include_doc` regions.
+///
+/// This borrows the input text. Use [`ApplyError::to_error`] to get an owned [`struct@Error`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ApplyError<'a> {
+    BadAttr(BadAttrError),
+    MissingAttr(Attr<'a>),
+    MismatchAttr {
+        start: Attr<'a>,
+        end: Attr<'a>,
+        mismatch: attr::Mismatch,
+    },
+    TextNotFound {
+        attr: Attr<'a>,
+        source: SourceText,
+        /// The lines of the included file similar to the text, the most similar first.
+        similar: Vec<Range<usize>>,
+    },
+    SourceRead {
+        attr: Attr<'a>,
+        reason: String,
+    },
+    NotDocComment {
+        attr: Attr<'a>,
+        range: Range<usize>,
+    },
+    /// Both the region and the included file changed since the region was generated,
+    /// or another region including the same lines was edited.
+    Conflict {
+        attr: Attr<'a>,
+        source_rel_path: PathBuf,
+    },
+    /// The region was edited, but its end attribute has no hash to tell whether the included file changed too.
+    MissingHash {
+        start: Attr<'a>,
+        end: Attr<'a>,
+    },
+    Edited {
+        start: Attr<'a>,
+        end: Attr<'a>,
+    },
+    Select {
+        attr: Attr<'a>,
+        error: SelectError,
+    },
+    NotReversible(Attr<'a>),
+    /// The included text contains `*/` or `/*`, which cannot be written in a block doc comment.
+    BlockComment(Attr<'a>),
+    /// The included files include each other with `<!-- include: {path} -->`.
+    IncludeCycle {
+        attr: Attr<'a>,
+        /// The relative paths from the file in the attribute to the file included again.
+        chain: Vec<PathBuf>,
+    },
+    /// The included text contains a `{{name}}` placeholder whose variable is not defined.
+    UndefinedVariable {
+        attr: Attr<'a>,
+        source: SourceText,
+        name: String,
+        /// The range of the placeholder in the included file.
+        range: Range<usize>,
+        /// The defined variable most similar to `name`.
+        similar: Option<String>,
+    },
+}
+impl ApplyError<'_> {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            ApplyError::BadAttr(_) => ErrorKind::BadAttr,
+            ApplyError::MissingAttr(_) => ErrorKind::MissingAttr,
+            ApplyError::MismatchAttr { .. } => ErrorKind::MismatchAttr,
+            ApplyError::TextNotFound { .. } => ErrorKind::TextNotFound,
+            ApplyError::SourceRead { .. } => ErrorKind::SourceRead,
+            ApplyError::NotDocComment { .. } => ErrorKind::NotDocComment,
+            ApplyError::Conflict { .. } => ErrorKind::Conflict,
+            ApplyError::MissingHash { .. } => ErrorKind::MissingHash,
+            ApplyError::Edited { .. } => ErrorKind::Edited,
+            ApplyError::Select { .. } => ErrorKind::Select,
+            ApplyError::NotReversible(_) => ErrorKind::NotReversible,
+            ApplyError::BlockComment(_) => ErrorKind::BlockComment,
+            ApplyError::IncludeCycle { .. } => ErrorKind::IncludeCycle,
+            ApplyError::UndefinedVariable { .. } => ErrorKind::UndefinedVariable,
+        }
+    }
+    pub fn line(&self, input: &str) -> usize {
+        to_line(input, self.offset())
+    }
+    fn offset(&self) -> usize {
+        match self {
+            ApplyError::BadAttr(e) => e.range().start,
+            ApplyError::MissingAttr(attr)
+            | ApplyError::TextNotFound { attr, .. }
+            | ApplyError::SourceRead { attr, .. }
+            | ApplyError::Conflict { attr, .. }
+            | ApplyError::Select { attr, .. }
+            | ApplyError::NotReversible(attr)
+            | ApplyError::BlockComment(attr)
+            | ApplyError::IncludeCycle { attr, .. }
+            | ApplyError::UndefinedVariable { attr, .. } => attr.range.start,
+            ApplyError::MismatchAttr { start, .. }
+            | ApplyError::Edited { start, .. }
+            | ApplyError::MissingHash { start, .. } => start.range.start,
+            ApplyError::NotDocComment { range, .. } => range.start,
+        }
+    }
+    pub fn to_error(&self, rel_path: &Path, input: &str) -> Error {
+        Error {
+            kind: self.kind(),
+            rel_path: rel_path.to_path_buf(),
+            line: Some(self.line(input)),
+            message: self.to_error_message(rel_path, input),
+        }
+    }
+    pub fn to_error_message(&self, rel_path: &Path, input: &str) -> String {
+        let (summary, labels, help) = self.diagnostic(input);
+        let mut message = format!(
+            "{summary}\n{}",
+            fmt_snippet(rel_path, input, &labels, help.as_deref())
+        );
+        if let Some((note, source, labels)) = self.source_note() {
+            use yansi::Paint;
+            write!(message, "\n{}: {note}", "note".bold()).unwrap();
+            if !labels.is_empty() {
+                let snippet = fmt_snippet(&source.rel_path, &source.text, &labels, None);
+                write!(message, "\n{snippet}").unwrap();
+            }
+        }
+        message
+    }
+    /// Returns the parts of the message for editors, which show the location and the source by themselves.
+    pub fn to_diagnostic(&self, input: &str) -> Diagnostic {
+        let (summary, labels, help) = self.diagnostic(input);
+        let range = labels
+            .iter()
+            .find(|label| label.is_primary())
+            .map_or_else(|| self.offset()..self.offset(), |label| label.range());
+        Diagnostic {
+            kind: self.kind(),
+            range,
+            summary,
+            help,
+        }
+    }
+    /// Returns the note about the included file, with its labeled parts.
+    fn source_note(&self) -> Option<(String, &SourceText, Vec<Label>)> {
+        match self {
+            ApplyError::TextNotFound {
+                source, similar, ..
+            } => {
+                if similar.is_empty() {
+                    let note = format!("no similar lines in `{}`", source.rel_path.display());
+                    return Some((note, source, Vec::new()));
+                }
+                let labels = similar
+                    .iter()
+                    .enumerate()
+                    .map(|(i, range)| {
+                        if i == 0 {
+                            Label::primary(range.clone(), "most similar")
+                        } else {
+                            Label::secondary(range.clone(), "")
+                        }
+                    })
+                    .collect();
+                let note = format!("similar lines in `{}`", source.rel_path.display());
+                Some((note, source, labels))
+            }
+            ApplyError::UndefinedVariable { source, range, .. } => {
+                let labels = vec![Label::primary(range.clone(), "undefined")];
+                let note = format!("used in `{}`", source.rel_path.display());
+                Some((note, source, labels))
+            }
+            _ => None,
+        }
+    }
+    /// Returns the first line of the message, the labeled parts of `input` and the help message.
+    fn diagnostic(&self, input: &str) -> (String, Vec<Label>, Option<String>) {
+        match self {
+            ApplyError::BadAttr(e) => (
+                e.to_string(),
+                vec![Label::primary(e.span(), "")],
+                e.help().map(str::to_string),
+            ),
+            ApplyError::MissingAttr(attr) => {
+                let (msg, label, action, position) = match attr.action {
+                    attr::Action::Start => (
+                        "missing end attribute",
+                        "this start attribute has no end",
+                        attr::Action::End,
+                        "after",
+                    ),
+                    attr::Action::End => (
+                        "missing start attribute",
+                        "this end attribute has no start",
+                        attr::Action::Start,
+                        "before",
+                    ),
+                };
+                let pair = Attr {
+                    action,
+                    arg: attr::ActionArg::None,
+                    options: Vec::new(),
+                    ..attr.clone()
+                };
+                (
+                    msg.into(),
+                    vec![Label::primary(attr.range(), label)],
+                    Some(format!(
+                        "add `{}` {position} the generated text",
+                        pair.to_source()
+                    )),
+                )
+            }
+            ApplyError::MismatchAttr {
+                start,
+                end,
+                mismatch,
+            } => {
+                let labels = match mismatch {
+                    attr::Mismatch::Kind => vec![
+                        Label::primary(end.range(), format!("this is {}", describe_kind(end.kind))),
+                        Label::secondary(
+                            start.range(),
+                            format!("the start attribute is {}", describe_kind(start.kind)),
+                        ),
+                    ],
+                    attr::Mismatch::Path => vec![
+                        Label::primary(
+                            end.spans(input).path,
+                            format!("expected `\"{}\"`", start.path),
+                        ),
+                        Label::secondary(
+                            start.spans(input).path,
+                            "the path of the start attribute",
+                        ),
+                    ],
+                };
+                (mismatch.message().into(), labels, None)
+            }
+            ApplyError::TextNotFound {
+                attr,
+                source,
+                similar,
+            } => {
+                let (msg, action) = match attr.action {
+                    attr::Action::Start => ("start text not found", "start"),
+                    attr::Action::End => ("end text not found", "end"),
+                };
+                let span = attr.spans(input).arg.unwrap_or_else(|| attr.range());
+                let help = similar.first().map(|range| {
+                    format!(
+                        "did you mean `{action}(\"{}\")`?",
+                        &source.text[range.clone()]
+                    )
+                });
+                (
+                    msg.into(),
+                    vec![Label::primary(
+                        span,
+                        format!("not found in `{}`", attr.path),
+                    )],
+                    help,
+                )
+            }
+            ApplyError::SourceRead { attr, reason } => (
+                format!("cannot read `{}`", attr.path),
+                vec![Label::primary(attr.spans(input).path, reason.as_str())],
+                None,
+            ),
+            ApplyError::NotDocComment { attr, range } => {
+                let line = input[range.clone()].trim_end_matches(['\r', '\n']);
+                let msg = format!(
+                    "cannot write back to `{}` because a line in the region is not a doc comment",
+                    attr.path
+                );
+                (
+                    msg,
+                    vec![
+                        Label::secondary(attr.range(), "the region starts here"),
+                        Label::primary(range.start..range.start + line.len(), "not a doc comment"),
+                    ],
+                    None,
+                )
+            }
+            ApplyError::Conflict {
+                attr,
+                source_rel_path,
+            } => (
+                format!(
+                    "the edits in this region conflict with changes to `{}`",
+                    source_rel_path.display()
+                ),
+                vec![Label::primary(attr.spans(input).path, "included here")],
+                Some(
+                    "merge the changes by hand, or run without `--reverse` to discard the edits"
+                        .into(),
+                ),
+            ),
+            ApplyError::MissingHash { start, end } => (
+                format!(
+                    "cannot write back to `{}` because the end attribute has no hash",
+                    start.path
+                ),
+                vec![
+                    Label::secondary(start.range(), "the region starts here"),
+                    Label::primary(
+                        end.range(),
+                        "no `hash` to detect changes to the included file",
+                    ),
+                ],
+                Some("run with `--hash` before editing regions to write back".into()),
+            ),
+            ApplyError::NotReversible(attr) => {
+                let spans = attr.spans(input);
+                let mut labels: Vec<_> = attr
+                    .options
+                    .iter()
+                    .zip(spans.options)
+                    .filter(|(o, _)| matches!(o.name, "heading_offset" | "hide" | "code"))
+                    .map(|(_, span)| Label::primary(span, "transforms the included text"))
+                    .collect();
+                if matches!(
+                    attr.arg,
+                    attr::ActionArg::Item(_) | attr::ActionArg::Anchor(_)
+                ) {
+                    labels.insert(
+                        0,
+                        Label::primary(spans.action, "removes lines from the included text"),
+                    );
+                }
+                if labels.is_empty() {
+                    labels.push(Label::primary(
+                        attr.range(),
+                        "the included text includes other files or variables",
+                    ));
+                }
+                (
+                    format!(
+                        "cannot write back to `{}` because the included text is transformed",
+                        attr.path
+                    ),
+                    labels,
+                    Some("edit the included file directly".into()),
+                )
+            }
+            ApplyError::BlockComment(attr) => (
+                format!(
+                    "cannot include `{}` in a block doc comment because it contains `*/` or `/*`",
+                    attr.path
+                ),
+                vec![Label::primary(
+                    attr.option_span(input, "style"),
+                    "block doc comment",
+                )],
+                Some("use another `style`".into()),
+            ),
+            ApplyError::Select { attr, error } => {
+                let target = attr.arg.selector_description().unwrap_or_default();
+                let (msg, label) = match error {
+                    SelectError::NotFound => (
+                        format!("{target} not found in `{}`", attr.path),
+                        "not found".to_string(),
+                    ),
+                    SelectError::Duplicated { lines } => (
+                        format!("{target} is defined more than once in `{}`", attr.path),
+                        format!(
+                            "defined at lines {}",
+                            lines
+                                .iter()
+                                .map(|l| l.to_string())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ),
+                    ),
+                    SelectError::Unclosed { line } => (
+                        format!("{target} in `{}` is not closed", attr.path),
+                        format!("opened at line {line}"),
+                    ),
+                };
+                (
+                    msg,
+                    vec![Label::primary(attr.spans(input).action, label)],
+                    None,
+                )
+            }
+            ApplyError::IncludeCycle { attr, chain } => {
+                let chain: Vec<_> = chain
+                    .iter()
+                    .map(|path| format!("`{}`", path.display()))
+                    .collect();
+                (
+                    format!("cyclic include: {}", chain.join(" -> ")),
+                    vec![Label::primary(attr.spans(input).path, "included here")],
+                    Some("remove one of the `<!-- include: ... -->` lines in the chain".into()),
+                )
+            }
+            ApplyError::UndefinedVariable {
+                attr,
+                name,
+                similar,
+                ..
+            } => (
+                format!("undefined variable `{name}` in `{}`", attr.path),
+                vec![Label::primary(attr.spans(input).path, "included here")],
+                Some(match similar {
+                    Some(similar) => format!("did you mean `{{{{{similar}}}}}`?"),
+                    None => format!("define it with `--define {name}=...`"),
+                }),
+            ),
+            ApplyError::Edited { start, end } => (
+                "the region was edited after it was generated".into(),
+                vec![
+                    Label::secondary(start.range(), "the region starts here"),
+                    Label::primary(
+                        end.option_span(input, "hash"),
+                        "the text does not match this hash",
+                    ),
+                ],
+                Some(
+                    "run with `--reverse` to write back the edits, or `--force` to overwrite them"
+                        .into(),
+                ),
+            ),
+        }
+    }
+}
+
+fn describe_kind(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Inner => "an inner attribute `#![...]`",
+        Kind::Outer => "an outer attribute `#[...]`",
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Display)]
+#[display(style = "snake_case")]
+#[non_exhaustive]
+pub enum ErrorKind {
+    Read,
+    BadAttr,
+    MissingAttr,
+    MismatchAttr,
+    TextNotFound,
+    SourceRead,
+    NotDocComment,
+    Conflict,
+    Edited,
+    Select,
+    NotReversible,
+    BlockComment,
+    IncludeCycle,
+    UndefinedVariable,
+    MissingHash,
+}
+impl ErrorKind {
+    /// The stable code of this kind of error, such as `E0005`, shown as `error[E0005]`.
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorKind::Read => "E0001",
+            ErrorKind::BadAttr => "E0002",
+            ErrorKind::MissingAttr => "E0003",
+            ErrorKind::MismatchAttr => "E0004",
+            ErrorKind::TextNotFound => "E0005",
+            ErrorKind::SourceRead => "E0006",
+            ErrorKind::NotDocComment => "E0007",
+            ErrorKind::Conflict => "E0008",
+            ErrorKind::Edited => "E0009",
+            ErrorKind::Select => "E0010",
+            ErrorKind::NotReversible => "E0011",
+            ErrorKind::BlockComment => "E0012",
+            ErrorKind::IncludeCycle => "E0014",
+            ErrorKind::UndefinedVariable => "E0015",
+            ErrorKind::MissingHash => "E0016",
+        }
+    }
+}
+
+/// The parts of the message of an [`ApplyError`], returned by [`ApplyError::to_diagnostic`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Diagnostic {
+    pub kind: ErrorKind,
+    /// The byte range of the input text the error points at.
+    pub range: Range<usize>,
+    /// The first line of the message.
+    pub summary: String,
+    pub help: Option<String>,
+}
+
+/// An owned error with its location and formatted message.
+#[derive(Error, Clone, Debug)]
+#[error("{message}")]
+#[non_exhaustive]
+pub struct Error {
+    kind: ErrorKind,
+    rel_path: PathBuf,
+    line: Option<usize>,
+    message: String,
+}
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+    pub fn code(&self) -> &'static str {
+        self.kind.code()
+    }
+    /// The path of the `*.rs` file where the error occurred, relative to the root.
+    pub fn rel_path(&self) -> &Path {
+        &self.rel_path
+    }
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+    /// The first line of the message, without the location and the source.
+    pub fn summary(&self) -> &str {
+        self.message.lines().next().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use std::{
+        fs::{read, read_dir, DirEntry},
+        path::Path,
+    };
+    #[test]
+    fn test_convert_file() -> Result<()> {
+        use yansi::Paint;
+        let dir = Path::new("./tests/data");
+        for e in read_dir(dir)? {
+            let e = e?;
+            if let Some((input, expected)) = to_input_expected(e) {
+                eprint!("test {input} ... ");
+                match check_convert_file(dir, &dir.join(input), &dir.join(expected)) {
+                    Ok(_) => {
+                        eprintln!("{}", "ok".green());
+                    }
+                    Err(e) => {
+                        eprintln!("{}", "FAILED".red());
+                        bail!("{}", e)
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    fn to_input_expected(e: DirEntry) -> Option<(String, String)> {
+        if !e.file_type().ok()?.is_file() {
+            return None;
+        }
+        let path = e.path();
+        let name = path.file_name()?.to_str()?;
+        if !name.ends_with(".rs") || name.ends_with(".expected.rs") {
+            return None;
+        }
+        let mut name_expected = path.file_stem()?.to_str()?.to_string();
+        name_expected += ".expected.rs";
+        Some((name.to_string(), name_expected))
+    }
+    fn check_convert_file(dir: &Path, input_path: &Path, expected_path: &Path) -> Result<()> {
+        let input_str = String::from_utf8(read(input_path)?)?;
+        let expected_str = String::from_utf8(read(expected_path)?)?;
+        let input_rel_path = input_path.strip_prefix(dir).unwrap_or(input_path);
+        match apply_str(dir, dir, &input_str) {
+            Ok(x) => {
+                let output_str = if let Some(text) = &x.text {
+                    text
+                } else {
+                    &input_str
+                };
+                let output_str = output_str.trim();
+                let expected_str = expected_str.trim();
+                if output_str != expected_str {
+                    bail!(
+                        "mismatch result\nexpected:\n{}\n\nactual:\n{}",
+                        expected_str,
+                        output_str
+                    );
+                }
+                Ok(())
+            }
+            Err(errors) => {
+                let messages: Vec<_> = errors
+                    .iter()
+                    .map(|e| e.to_error_message(input_rel_path, &input_str))
+                    .collect();
+                bail!("{}", messages.join("\n"))
+            }
+        }
+    }
+
+    #[test]
+    fn engine_apply_file_error() {
+        let engine = Engine::new("./tests/data");
+        let errors = engine
+            .apply_file(Path::new("./tests/data/not_exists.rs"))
+            .unwrap_err();
+        let e = &errors[0];
+        assert_eq!(e.kind(), ErrorKind::Read);
+        assert_eq!(e.rel_path(), Path::new("not_exists.rs"));
+    }
+    #[test]
+    fn engine_apply_file_with_logs() {
+        let engine = Engine::new("./tests/data");
+        let e = engine
+            .apply_file_with_logs(Path::new("./tests/data/errors/partial.rs"))
+            .unwrap_err();
+        assert_eq!(e.errors.len(), 1);
+        assert_eq!(e.errors[0].line(), Some(5));
+        assert_eq!(e.logs.len(), 1);
+        assert_eq!(e.logs[0].line, 1);
+    }
+
+    #[test]
+    fn error_message_mismatch() {
+        yansi::disable();
+        let dir = Path::new("./tests/data");
+        let input = r#"// #[include_doc("doc.md", start)]
+/// text
+// #[include_doc("doc_long.md", end)]
+"#;
+        let errors = apply_str(dir, dir, input).unwrap_err();
+        let e = errors[0].to_error(Path::new("lib.rs"), input);
+        assert_eq!(e.code(), "E0004");
+        assert_eq!(
+            e.to_string(),
+            r#"the path of the end attribute does not match the start attribute
+ --> lib.rs:3:18
+  |
+1 | // #[include_doc("doc.md", start)]
+  |                  -------- the path of the start attribute
+...
+3 | // #[include_doc("doc_long.md", end)]
+  |                  ^^^^^^^^^^^^^ expected `"doc.md"`"#
+        );
+    }
+
+    #[test]
+    fn error_text_not_found() {
+        yansi::disable();
+        let dir = Path::new("./tests/data");
+        let input = r###"// #[include_doc("doc_sections.md", start("## Instalation"))]
+// #[include_doc("doc_sections.md", end)]
+"###;
+        let errors = apply_str(dir, dir, input).unwrap_err();
+        let e = errors[0].to_error(Path::new("lib.rs"), input);
+        assert_eq!(e.kind(), ErrorKind::TextNotFound);
+        assert_eq!(
+            e.to_string(),
+            r###"start text not found
+ --> lib.rs:1:42
+  |
+1 | // #[include_doc("doc_sections.md", start("## Instalation"))]
+  |                                          ^^^^^^^^^^^^^^^^^^ not found in `doc_sections.md`
+  |
+  = help: did you mean `start("## Install")`?
+note: similar lines in `doc_sections.md`
+ --> doc_sections.md:3:1
+  |
+3 | ## Install
+  | ^^^^^^^^^^ most similar"###
+        );
+    }
+    #[test]
+    fn apply_ambiguous_text() {
+        let dir = Path::new("./tests/data");
+        let input = r###"// #[include_doc("doc_fences.md", start("```rust"))]
+// #[include_doc("doc_fences.md", end("```"))]
+"###;
+        let result = apply_str(dir, dir, input).unwrap();
+        let log = &result.logs[0];
+        assert_eq!(log.new_text, "/// ```rust\n/// fn a() {}\n");
+        let ambiguous: Vec<_> = log
+            .ambiguous_texts
+            .iter()
+            .map(|a| (a.line, a.text.as_str(), a.source_lines.clone()))
+            .collect();
+        assert_eq!(
+            ambiguous,
+            [(1, "```rust", vec![3, 7]), (2, "```", vec![5, 7, 9])]
+        );
+    }
+    #[test]
+    fn apply_text_first_start() {
+        let dir = Path::new("./tests/data");
+        let input = r###"// #[include_doc("doc_sections.md", start("## "))]
+// #[include_doc("doc_sections.md", end("## Usage"))]
+"###;
+        let result = apply_str(dir, dir, input).unwrap();
+        assert_eq!(
+            result.logs[0].new_text,
+            "/// ## Install\n///\n/// cargo install\n"
+        );
+    }
+    #[test]
+    fn apply_end_text_before_start() {
+        let dir = Path::new("./tests/data");
+        let input = r###"// #[include_doc("doc_sections.md", start("## Usage"))]
+// #[include_doc("doc_sections.md", end("## Install"))]
+"###;
+        let errors = apply_str(dir, dir, input).unwrap_err();
+        assert_eq!(errors[0].kind(), ErrorKind::TextNotFound);
+    }
+    #[test]
+    fn apply_nested_source_lines() {
+        let dir = Path::new("./tests/data");
+        let input = r#"// #[include_doc("doc_nested.md", start("example"))]
+// #[include_doc("doc_nested.md", end)]
+"#;
+        let result = apply_str(dir, dir, input).unwrap();
+        let log = &result.logs[0];
+        assert_eq!(log.new_text, "/// example\n///\n/// End\n");
+        assert_eq!(log.source_lines, 3..6);
+        assert_eq!(log.source_range, 8..41);
+    }
+    #[test]
+    fn error_text_not_found_nested() {
+        yansi::disable();
+        let dir = Path::new("./tests/data");
+        let input = r#"// #[include_doc("doc_nested.md", start("exampel"))]
+// #[include_doc("doc_nested.md", end)]
+"#;
+        let errors = apply_str(dir, dir, input).unwrap_err();
+        let e = errors[0].to_error(Path::new("lib.rs"), input);
+        let message = e.to_string();
+        assert!(
+            message.ends_with(
+                r#"note: similar lines in `doc_use.md`
+ --> doc_use.md:1:1
+  |
+1 | example
+  | ^^^^^^^ most similar"#
+            ),
+            "{message}"
+        );
+    }
+    #[test]
+    fn error_include_cycle() {
+        yansi::disable();
+        let dir = Path::new("./tests/data");
+        let input = r#"// #[include_doc("cycle_a.md", start)]
+// #[include_doc("cycle_a.md", end)]
+"#;
+        let errors = apply_str(dir, dir, input).unwrap_err();
+        let e = errors[0].to_error(Path::new("lib.rs"), input);
+        assert_eq!(e.kind(), ErrorKind::IncludeCycle);
+        assert_eq!(
+            e.to_string(),
+            r#"cyclic include: `cycle_a.md` -> `cycle_b.md` -> `cycle_a.md`
+ --> lib.rs:1:18
+  |
+1 | // #[include_doc("cycle_a.md", start)]
+  |                  ^^^^^^^^^^^^ included here
+  |
+  = help: remove one of the `<!-- include: ... -->` lines in the chain"#
+        );
+    }
+
+    #[test]
+    fn error_undefined_variable() {
+        yansi::disable();
+        let dir = Path::new("./tests/data");
+        let input = r#"// #[include_doc("doc_undefined.md", start)]
+// #[include_doc("doc_undefined.md", end)]
+"#;
+        let errors = apply_str(dir, dir, input).unwrap_err();
+        let e = errors[0].to_error(Path::new("lib.rs"), input);
+        assert_eq!(e.code(), "E0015");
+        assert_eq!(
+            e.to_string(),
+            r#"undefined variable `crate.verison` in `doc_undefined.md`
+ --> lib.rs:1:18
+  |
+1 | // #[include_doc("doc_undefined.md", start)]
+  |                  ^^^^^^^^^^^^^^^^^^ included here
+  |
+  = help: did you mean `{{crate.version}}`?
+note: used in `doc_undefined.md`
+ --> doc_undefined.md:1:9
+  |
+1 | Version {{crate.verison}}
+  |         ^^^^^^^^^^^^^^^^^ undefined"#
+        );
+    }
+    #[test]
+    fn apply_define() {
+        let dir = Path::new("./tests/data");
+        let input = r#"// #[include_doc("doc_undefined.md", start)]
+// #[include_doc("doc_undefined.md", end)]
+"#;
+        let engine = Engine::new(dir).define("crate.verison", "1.0");
+        let result = engine.apply_str(dir, input).unwrap();
+        assert_eq!(result.logs[0].new_text, "/// Version 1.0\n");
+    }
+
+    #[test]
+    fn apply_collects_all_errors() {
+        let dir = Path::new("./tests/data");
+        let input = r#"// #[include_doc("doc.md", unknown)]
+// #[include_doc("doc.md", end)]
+// #[include_doc("doc.md", start)]
+// #[include_doc("doc_long.md", end)]
+// #[include_doc("doc.md", start("not found"))]
+// #[include_doc("doc.md", end)]
+// #[include_doc("not_exists.md", start)]
+// #[include_doc("not_exists.md", end)]
+// #[include_doc("doc.md", start)]
+"#;
+        let errors = apply_str(dir, dir, input).unwrap_err();
+        let kinds: Vec<_> = errors.iter().map(|e| e.kind()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ErrorKind::BadAttr,
+                ErrorKind::MissingAttr,
+                ErrorKind::MismatchAttr,
+                ErrorKind::TextNotFound,
+                ErrorKind::SourceRead,
+                ErrorKind::MissingAttr,
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_edited() {
+        let dir = Path::new("./tests/data");
+        let input = r#"// #[include_doc("doc.md", start)]
+/// edited by hand
+// #[include_doc("doc.md", end, hash("d7954c6522ad816e"))]
+"#;
+        let errors = apply_str(dir, dir, input).unwrap_err();
+        assert_eq!(errors[0].kind(), ErrorKind::Edited);
+
+        let result = Engine::new(dir).force(true).apply_str(dir, input).unwrap();
+        assert_eq!(
+            result.text.unwrap(),
+            r#"// #[include_doc("doc.md", start)]
+/// this is example function.
+// #[include_doc("doc.md", end, hash("d7954c6522ad816e"))]
+"#
+        );
+    }
+    #[test]
+    fn apply_block_comment_end() {
+        let dir = Path::new("./tests/data");
+        let input = r#"// #[include_doc("doc_glob.md", start, style("block"))]
+// #[include_doc("doc_glob.md", end)]
+"#;
+        let errors = apply_str(dir, dir, input).unwrap_err();
+        assert_eq!(errors[0].kind(), ErrorKind::BlockComment);
+    }
+    #[test]
+    fn apply_engine_defaults() {
+        let dir = Path::new("./tests/data");
+        let input = r#"// #[include_doc("doc_quote.md", start)]
+// #[include_doc("doc_quote.md", end)]
+// #[include_doc("doc.md", start, style("comment"))]
+// #[include_doc("doc.md", end)]
+"#;
+        let engine = Engine::new(dir).style(DocStyle::Attr).heading_offset(1);
+        assert_eq!(
+            engine.apply_str(dir, input).unwrap().text.unwrap(),
+            r#"// #[include_doc("doc_quote.md", start)]
+#[doc = " ## Greeting"]
+#[doc = ""]
+#[doc = " Prints \"hello\" followed by `\\n`."]
+// #[include_doc("doc_quote.md", end)]
+// #[include_doc("doc.md", start, style("comment"))]
+/// this is example function.
+// #[include_doc("doc.md", end)]
+"#
+        );
+
+        let engine = Engine::new(dir).line_ending(LineEnding::CrLf);
+        let text = engine.apply_str(dir, input).unwrap().text.unwrap();
+        assert!(text.contains("/// this is example function.\r\n"));
+    }
+    #[test]
+    fn apply_engine_heading_offset_hide() {
+        let dir = Path::new("./tests/data");
+        let input = r#"// #[include_doc("examples/hidden.rs", start, hide("^use "))]
+// #[include_doc("examples/hidden.rs", end)]
+"#;
+        let engine = Engine::new(dir).heading_offset(1);
+        let result = engine.apply_str(dir, input).unwrap();
+        assert!(result.logs[0].new_text.starts_with("/// # use "));
+    }
+    #[test]
+    fn apply_record_hash() {
+        let dir = Path::new("./tests/data");
+        let input = r#"// #[include_doc("doc.md", start)]
+/// this is example function.
+    // #[include_doc("doc.md", end)]
+"#;
+        let result = Engine::new(dir)
+            .record_hash(true)
+            .apply_str(dir, input)
+            .unwrap();
+        assert_eq!(
+            result.text.unwrap(),
+            r#"// #[include_doc("doc.md", start)]
+/// this is example function.
+    // #[include_doc("doc.md", end, hash("d7954c6522ad816e"))]
+"#
+        );
+    }
+    #[test]
+    fn diff_hash_only() {
+        yansi::disable();
+        let dir = Path::new("./tests/data");
+        let input = r#"// #[include_doc("doc.md", start)]
+/// this is example function.
+// #[include_doc("doc.md", end)]
+"#;
+        let result = Engine::new(dir)
+            .record_hash(true)
+            .apply_str(dir, input)
+            .unwrap();
+        assert_eq!(
+            result.to_diff(Path::new("lib.rs")),
+            r#"--- a/lib.rs
++++ b/lib.rs
+@@ -2,2 +2,2 @@
+ /// this is example function.
+-// #[include_doc("doc.md", end)]
++// #[include_doc("doc.md", end, hash("d7954c6522ad816e"))]
+"#
+        );
+    }
+}
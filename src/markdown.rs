@@ -0,0 +1,333 @@
+use std::ops::Range;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum HeadingKind {
+    /// `# Title`
+    Atx,
+    /// `Title` followed by `===` or `---`.
+    Setext,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Heading<'a> {
+    pub kind: HeadingKind,
+    pub level: usize,
+    pub text: &'a str,
+    /// The range of the heading line, and the underline for setext headings, including the line break.
+    pub range: Range<usize>,
+}
+
+/// A line of text with the range of its content, excluding the line break.
+struct Line<'a> {
+    content: &'a str,
+    range: Range<usize>,
+}
+
+fn lines(text: &str) -> Vec<Line<'_>> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\r', '\n']);
+        lines.push(Line {
+            content,
+            range: offset..offset + line.len(),
+        });
+        offset += line.len();
+    }
+    lines
+}
+
+/// Returns the line without up to 3 spaces of indentation, or `None` if it is indented more.
+fn unindent(line: &str) -> Option<&str> {
+    let s = line.trim_start_matches(' ');
+    if line.len() - s.len() <= 3 {
+        Some(s)
+    } else {
+        None
+    }
+}
+
+/// Returns the fence character and its length if the line starts a fenced code block.
+fn fence(line: &str) -> Option<(char, usize)> {
+    let s = unindent(line)?;
+    let c = s.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = s.len() - s.trim_start_matches(c).len();
+    if len >= 3 {
+        Some((c, len))
+    } else {
+        None
+    }
+}
+
+fn atx_heading(line: &str) -> Option<(usize, &str)> {
+    let s = unindent(line)?;
+    let level = s.len() - s.trim_start_matches('#').len();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let rest = &s[level..];
+    if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    let mut text = rest.trim();
+    let closed = text.trim_end_matches('#');
+    if closed.is_empty() || closed.ends_with([' ', '\t']) {
+        text = closed.trim_end();
+    }
+    Some((level, text))
+}
+
+fn setext_underline(line: &str) -> Option<usize> {
+    let s = unindent(line)?.trim_end();
+    if !s.is_empty() && s.chars().all(|c| c == '=') {
+        Some(1)
+    } else if !s.is_empty() && s.chars().all(|c| c == '-') {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+/// Returns the headings in `text`, ignoring lines in fenced code blocks.
+///
+/// Setext headings are recognized only when the text line is not a continuation of a paragraph.
+pub fn headings(text: &str) -> Vec<Heading<'_>> {
+    let lines = lines(text);
+    let mut headings = Vec::new();
+    let mut fence_open: Option<(char, usize)> = None;
+    let mut paragraph = false;
+    let mut index = 0;
+    while let Some(line) = lines.get(index) {
+        index += 1;
+        if let Some((c, len)) = fence_open {
+            if is_closing_fence(line.content, c, len) {
+                fence_open = None;
+            }
+            continue;
+        }
+        if let Some(f) = fence(line.content) {
+            fence_open = Some(f);
+            paragraph = false;
+        } else if let Some((level, heading_text)) = atx_heading(line.content) {
+            headings.push(Heading {
+                kind: HeadingKind::Atx,
+                level,
+                text: heading_text,
+                range: line.range.clone(),
+            });
+            paragraph = false;
+        } else if line.content.trim().is_empty() {
+            paragraph = false;
+        } else if let Some(level) = lines
+            .get(index)
+            .filter(|_| !paragraph && unindent(line.content).is_some())
+            .and_then(|next| setext_underline(next.content))
+        {
+            headings.push(Heading {
+                kind: HeadingKind::Setext,
+                level,
+                text: line.content.trim(),
+                range: line.range.start..lines[index].range.end,
+            });
+            index += 1;
+        } else {
+            paragraph = true;
+        }
+    }
+    headings
+}
+fn is_closing_fence(line: &str, c: char, len: usize) -> bool {
+    match fence(line) {
+        Some((c_close, len_close)) => {
+            c_close == c
+                && len_close >= len
+                && unindent(line).is_some_and(|s| s.trim_start_matches(c).trim().is_empty())
+        }
+        None => false,
+    }
+}
+
+/// An `<!-- include: {path} -->` line.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct IncludeDirective<'a> {
+    pub path: &'a str,
+    /// The range of the line, excluding the line break.
+    pub range: Range<usize>,
+}
+
+/// Returns the `<!-- include: {path} -->` lines in `text`, ignoring lines in fenced code blocks.
+pub fn include_directives(text: &str) -> Vec<IncludeDirective<'_>> {
+    let mut directives = Vec::new();
+    let mut fence_open: Option<(char, usize)> = None;
+    for line in lines(text) {
+        if let Some((c, len)) = fence_open {
+            if is_closing_fence(line.content, c, len) {
+                fence_open = None;
+            }
+            continue;
+        }
+        if let Some(f) = fence(line.content) {
+            fence_open = Some(f);
+        } else if let Some(path) = include_directive(line.content) {
+            directives.push(IncludeDirective {
+                path,
+                range: line.range.start..line.range.start + line.content.len(),
+            });
+        }
+    }
+    directives
+}
+fn include_directive(line: &str) -> Option<&str> {
+    let s = unindent(line)?.trim_end();
+    let s = s.strip_prefix("<!--")?.strip_suffix("-->")?.trim();
+    let path = s.strip_prefix("include:")?.trim();
+    (!path.is_empty()).then_some(path)
+}
+
+/// Changes the level of headings in `text` by `offset`, keeping it between 1 and 6.
+///
+/// Setext headings whose new level is greater than 2 are converted to ATX headings.
+pub fn shift_headings(text: &str, offset: isize) -> String {
+    let mut s = String::new();
+    let mut last = 0;
+    for h in headings(text) {
+        s.push_str(&text[last..h.range.start]);
+        last = h.range.end;
+        let level = (h.level as isize + offset).clamp(1, 6) as usize;
+        let source = &text[h.range.clone()];
+        match h.kind {
+            HeadingKind::Atx => {
+                let indent = source.len() - source.trim_start_matches(' ').len();
+                s.push_str(&source[..indent]);
+                s.push_str(&"#".repeat(level));
+                s.push_str(&source[indent + h.level..]);
+            }
+            HeadingKind::Setext => {
+                let underline_start = source.find('\n').map_or(source.len(), |i| i + 1);
+                let (line, underline) = source.split_at(underline_start);
+                if level <= 2 {
+                    let c = if level == 1 { '=' } else { '-' };
+                    s.push_str(line);
+                    s.push_str(&underline.replace(['=', '-'], &c.to_string()));
+                } else {
+                    let eol = &underline[underline.trim_end_matches(['\r', '\n']).len()..];
+                    s.push_str(&"#".repeat(level));
+                    s.push(' ');
+                    s.push_str(h.text);
+                    s.push_str(eol);
+                }
+            }
+        }
+    }
+    s.push_str(&text[last..]);
+    s
+}
+
+/// Returns the info string of a fenced code block for a file with `extension`.
+pub fn code_info_string(extension: &str) -> &str {
+    match extension {
+        "rs" => "rust",
+        "sh" | "bash" => "sh",
+        "py" => "python",
+        "js" | "mjs" => "javascript",
+        "ts" => "typescript",
+        "yml" | "yaml" => "yaml",
+        "md" => "markdown",
+        "txt" => "text",
+        _ => extension,
+    }
+}
+
+/// Wraps `text` in a fenced code block that is longer than any run of backticks in `text`.
+pub fn to_code_block(text: &str, info_string: &str) -> String {
+    let mut max_run = 0;
+    let mut run = 0;
+    for c in text.chars() {
+        if c == '`' {
+            run += 1;
+            max_run = max_run.max(run);
+        } else {
+            run = 0;
+        }
+    }
+    let fence = "`".repeat((max_run + 1).max(3));
+    format!("{fence}{info_string}\n{text}\n{fence}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(text: &str, expected: Vec<(usize, &str)>) {
+        let headings: Vec<_> = headings(text).iter().map(|h| (h.level, h.text)).collect();
+        assert_eq!(headings, expected);
+    }
+
+    #[test]
+    fn atx() {
+        check(
+            "# A\ntext\n## B ##\n####### C\n#D\n",
+            vec![(1, "A"), (2, "B")],
+        );
+    }
+    #[test]
+    fn setext() {
+        check("A\n===\n\nB\n---\ntext\n", vec![(1, "A"), (2, "B")]);
+    }
+    #[test]
+    fn fenced_code() {
+        check("```sh\n# comment\n```\n# A\n", vec![(1, "A")]);
+    }
+
+    #[test]
+    fn include_directive_lines() {
+        let text = "a\n<!-- include: shared/b.md -->\r\n```md\n<!-- include: c.md -->\n```\n    <!-- include: d.md -->\n";
+        let directives = include_directives(text);
+        assert_eq!(
+            directives,
+            vec![IncludeDirective {
+                path: "shared/b.md",
+                range: 2..31,
+            }]
+        );
+    }
+
+    #[test]
+    fn shift_atx() {
+        assert_eq!(
+            shift_headings("# A\ntext\n## B ##\n", 1),
+            "## A\ntext\n### B ##\n"
+        );
+    }
+    #[test]
+    fn shift_cap() {
+        assert_eq!(shift_headings("##### A\n# B", 2), "###### A\n### B");
+        assert_eq!(shift_headings("## A\n", -3), "# A\n");
+    }
+    #[test]
+    fn shift_setext() {
+        assert_eq!(
+            shift_headings("A\r\n===\r\n\r\nB\r\n---\r\n", 1),
+            "A\r\n---\r\n\r\n### B\r\n"
+        );
+    }
+    #[test]
+    fn shift_fenced_code() {
+        assert_eq!(
+            shift_headings("```sh\n# comment\n```\n", 1),
+            "```sh\n# comment\n```\n"
+        );
+    }
+
+    #[test]
+    fn code_block() {
+        assert_eq!(
+            to_code_block("fn main() {}", "rust"),
+            "```rust\nfn main() {}\n```"
+        );
+        assert_eq!(
+            to_code_block("```sh\nls\n````", "md"),
+            "`````md\n```sh\nls\n````\n`````"
+        );
+    }
+}
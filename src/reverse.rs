@@ -0,0 +1,384 @@
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    attr::{DocStyle, Kind},
+    detect_eol, end_attr_with_hash, find_pairs, region_range, region_text, substitute, text_hash,
+    to_doc_comment, trim_range, ApplyError, Engine,
+};
+
+/// The result of writing the edits made to doc comments in a `*.rs` file back to the included files.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct ReverseResult {
+    /// The included files to update.
+    pub updates: Vec<SourceUpdate>,
+    /// The text of the `*.rs` file with the hashes of the written back regions updated,
+    /// or `None` if no region was written back.
+    pub text: Option<String>,
+}
+
+/// The new text of an included file, with the edits made to the doc comments that include it.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct SourceUpdate {
+    pub path: PathBuf,
+    /// The path of the included file, relative to the root.
+    pub rel_path: PathBuf,
+    pub text: String,
+    /// The line numbers of the start attributes whose regions were written back.
+    pub lines: Vec<usize>,
+}
+
+struct Source {
+    rel_path: PathBuf,
+    text: String,
+    edits: Vec<Edit>,
+}
+struct Edit {
+    range: Range<usize>,
+    text: String,
+    line: usize,
+}
+
+/// Writes the edits made to `include_doc` regions in `input` back to the included files.
+///
+/// See [`Engine::reverse_str`] for how edited regions are detected.
+pub fn reverse_str<'a>(
+    root: &Path,
+    base: &Path,
+    input: &'a str,
+) -> Result<ReverseResult, Vec<ApplyError<'a>>> {
+    Engine::new(root).reverse_str(base, input)
+}
+
+impl Engine {
+    /// Writes the edits made to `include_doc` regions in `input` back to the included files.
+    ///
+    /// The hash recorded in the end attribute tells which side changed since the region was generated.
+    /// A region whose included file changed as well is reported as a conflict,
+    /// and a region without a hash is reported as an error, because the edits could overwrite changes in the included file.
+    ///
+    /// The hashes of the written back regions are updated so that the doc comments are in sync with the included files.
+    pub fn reverse_str<'a>(
+        &self,
+        base: &Path,
+        input: &'a str,
+    ) -> Result<ReverseResult, Vec<ApplyError<'a>>> {
+        let mut errors = Vec::new();
+        let mut sources = BTreeMap::new();
+        let mut end_attrs = Vec::new();
+        let eol = self.eol(input);
+        let vars = self.variables(base);
+        for (start, end) in find_pairs(input, &mut errors) {
+            let s = match self.include(base, start.path) {
+                Ok(s) => s,
+                Err(e) => {
+                    errors.push(e.into_apply_error(start));
+                    continue;
+                }
+            };
+            let range = match trim_range(&s, &start, &end, &mut Vec::new()) {
+                Ok(range) => range,
+                Err(e) => {
+                    errors.push(e);
+                    continue;
+                }
+            };
+            let style = self.doc_style(&start);
+            let old_text = region_text(input, &start, &end);
+            let text = match substitute(&s, range.clone(), &start, &vars) {
+                Ok(text) => text,
+                Err(e) => {
+                    errors.push(e);
+                    continue;
+                }
+            };
+            let new_text = self.transform(&text, &start);
+            let new_text = to_doc_comment(&new_text, start.kind, style, start.indent(input), eol);
+            if new_text == old_text {
+                continue;
+            }
+            let hash = end.hash();
+            if hash == Some(&text_hash(old_text)) {
+                // Only the included file changed, so there is nothing to write back.
+                continue;
+            }
+            let has_variables = matches!(text, Cow::Owned(_));
+            if self.is_transformed(&start) || !s.nested_paths.is_empty() || has_variables {
+                errors.push(ApplyError::NotReversible(start));
+                continue;
+            }
+            let Some(hash) = hash else {
+                errors.push(ApplyError::MissingHash { start, end });
+                continue;
+            };
+            if text_hash(&new_text) != hash {
+                errors.push(ApplyError::Conflict {
+                    attr: start,
+                    source_rel_path: s.rel_path,
+                });
+                continue;
+            }
+            let old_offset = region_range(input, &start, &end).start;
+            let text = match from_doc_comment(old_text, start.kind, style, detect_eol(&s.text)) {
+                Ok(text) => text,
+                Err(line_range) => {
+                    errors.push(ApplyError::NotDocComment {
+                        attr: start,
+                        range: line_range.start + old_offset..line_range.end + old_offset,
+                    });
+                    continue;
+                }
+            };
+            let source = sources.entry(s.path).or_insert_with(|| Source {
+                rel_path: s.rel_path,
+                text: s.text,
+                edits: Vec::new(),
+            });
+            if source
+                .edits
+                .iter()
+                .any(|e| e.range.start < range.end && range.start < e.range.end)
+            {
+                errors.push(ApplyError::Conflict {
+                    attr: start,
+                    source_rel_path: source.rel_path.clone(),
+                });
+                continue;
+            }
+            source.edits.push(Edit {
+                range,
+                text,
+                line: start.line(input),
+            });
+            end_attrs.push((
+                end.range(),
+                end_attr_with_hash(input, &end, &text_hash(old_text)),
+            ));
+        }
+        if !errors.is_empty() {
+            errors.sort_by_key(|e| e.offset());
+            return Err(errors);
+        }
+        let mut updates = Vec::new();
+        for (path, mut source) in sources {
+            source.edits.sort_by_key(|e| e.range.start);
+            let lines = source.edits.iter().map(|e| e.line).collect();
+            for e in source.edits.iter().rev() {
+                source.text.replace_range(e.range.clone(), &e.text);
+            }
+            updates.push(SourceUpdate {
+                path,
+                rel_path: source.rel_path,
+                text: source.text,
+                lines,
+            });
+        }
+        let text = (!end_attrs.is_empty()).then(|| {
+            let mut text = input.to_string();
+            for (range, end_attr) in end_attrs.into_iter().rev() {
+                text.replace_range(range, &end_attr);
+            }
+            text
+        });
+        Ok(ReverseResult { updates, text })
+    }
+}
+
+/// Removes the doc comment prefix, or the doc attribute around the text, from each line of `s`.
+///
+/// Returns the range of the first line that is not a doc comment on failure.
+fn from_doc_comment(
+    s: &str,
+    kind: Kind,
+    style: DocStyle,
+    eol: &str,
+) -> Result<String, Range<usize>> {
+    let block = match style {
+        DocStyle::RawAttr => from_raw_doc_attr(s, kind, eol),
+        DocStyle::Block => from_block_doc_comment(s, kind, eol),
+        _ => None,
+    };
+    if matches!(style, DocStyle::RawAttr | DocStyle::Block) {
+        return block.ok_or(0..s.lines().next().unwrap_or("").len());
+    }
+    let prefix = kind.doc_comment_prefix().trim_end();
+    let attr_start = format!("#{}[doc = \"", kind.attr_bang());
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for line in s.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\r', '\n']);
+        let text = match style {
+            DocStyle::Attr => content
+                .trim()
+                .strip_prefix(attr_start.as_str())
+                .and_then(|s| s.strip_suffix("\"]"))
+                .map(unescape),
+            _ => content
+                .trim_start()
+                .strip_prefix(prefix)
+                .map(|s| s.to_string()),
+        };
+        if let Some(text) = text {
+            lines.push(text.strip_prefix(' ').unwrap_or(&text).to_string());
+        } else {
+            return Err(offset..offset + content.len());
+        }
+        offset += line.len();
+    }
+    Ok(trim_blank_lines(&lines.join(eol)).to_string())
+}
+fn from_raw_doc_attr(s: &str, kind: Kind, eol: &str) -> Option<String> {
+    let s = s.trim();
+    let s = s.strip_prefix(&format!("#{}[doc = r", kind.attr_bang()))?;
+    let hashes = &s[..s.len() - s.trim_start_matches('#').len()];
+    let s = s[hashes.len()..].strip_prefix('"')?;
+    let s = s.strip_suffix(&format!("\"{hashes}]"))?;
+    Some(trim_blank_lines(&s.lines().collect::<Vec<_>>().join(eol)).to_string())
+}
+fn from_block_doc_comment(s: &str, kind: Kind, eol: &str) -> Option<String> {
+    let s = s.trim();
+    let s = s.strip_prefix(kind.block_doc_comment_start())?;
+    let s = s.strip_suffix("*/")?;
+    Some(trim_blank_lines(&s.lines().collect::<Vec<_>>().join(eol)).to_string())
+}
+
+/// Removes the leading blank lines and the trailing whitespace, keeping the indentation of the first line.
+fn trim_blank_lines(s: &str) -> &str {
+    let s = s.trim_end();
+    let text_start = s.len() - s.trim_start().len();
+    &s[s[..text_start].rfind('\n').map_or(0, |n| n + 1)..]
+}
+fn unescape(s: &str) -> String {
+    let mut r = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            r.extend(chars.next());
+        } else {
+            r.push(c);
+        }
+    }
+    r
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+
+    fn region(text: &str, hash_of: Option<&str>) -> String {
+        let end = match hash_of {
+            Some(generated) => format!("end, hash(\"{}\")", text_hash(generated)),
+            None => "end".into(),
+        };
+        format!(
+            "// #[include_doc(\"doc.md\", start)]\n{text}// #[include_doc(\"doc.md\", {end})]\n"
+        )
+    }
+    fn reverse_errors(input: &str) -> Vec<ErrorKind> {
+        let dir = Path::new("./tests/data");
+        match reverse_str(dir, dir, input) {
+            Ok(_) => Vec::new(),
+            Err(errors) => errors.iter().map(|e| e.kind()).collect(),
+        }
+    }
+
+    #[test]
+    fn reverse_edited_region() {
+        let dir = Path::new("./tests/data");
+        let input = region("/// edited\n", Some("/// this is example function.\n"));
+        let result = reverse_str(dir, dir, &input).unwrap();
+        assert_eq!(result.updates[0].text, "edited\n");
+        assert_eq!(
+            result.text.as_deref(),
+            Some(region("/// edited\n", Some("/// edited\n")).as_str())
+        );
+    }
+    #[test]
+    fn reverse_source_changed() {
+        let input = region("/// old\n", Some("/// old\n"));
+        assert_eq!(reverse_errors(&input), []);
+    }
+    #[test]
+    fn reverse_both_changed() {
+        let input = region("/// edited\n", Some("/// old\n"));
+        assert_eq!(reverse_errors(&input), [ErrorKind::Conflict]);
+    }
+    #[test]
+    fn reverse_without_hash() {
+        let input = region("/// edited\n", None);
+        assert_eq!(reverse_errors(&input), [ErrorKind::MissingHash]);
+        let input = region("/// this is example function.\n", None);
+        assert_eq!(reverse_errors(&input), []);
+    }
+
+    #[test]
+    fn from_doc_comment_ok() {
+        assert_eq!(
+            from_doc_comment(
+                "/// # Title\n///\n/// text\n",
+                Kind::Outer,
+                DocStyle::Comment,
+                "\n"
+            ),
+            Ok("# Title\n\ntext".to_string())
+        );
+    }
+    #[test]
+    fn from_doc_comment_indented() {
+        assert_eq!(
+            from_doc_comment(
+                "///\n///     indented code\n/// text  \n///\n",
+                Kind::Outer,
+                DocStyle::Comment,
+                "\n"
+            ),
+            Ok("    indented code\ntext".to_string())
+        );
+        assert_eq!(
+            from_doc_comment("/**\n    code\n*/\n", Kind::Outer, DocStyle::Block, "\n"),
+            Ok("    code".to_string())
+        );
+    }
+    #[test]
+    fn from_doc_comment_not_doc_comment() {
+        assert_eq!(
+            from_doc_comment("/// a\n// b\n", Kind::Outer, DocStyle::Comment, "\n"),
+            Err(6..10)
+        );
+    }
+    #[test]
+    fn from_doc_attr() {
+        assert_eq!(
+            from_doc_comment(
+                "#![doc = \" a \\\"b\\\\\"]\n#![doc = \"\"]\n",
+                Kind::Inner,
+                DocStyle::Attr,
+                "\n"
+            ),
+            Ok("a \"b\\".to_string())
+        );
+        assert_eq!(
+            from_doc_comment(
+                "#[doc = r#\"\n\"a\"\n\n\"#]\n",
+                Kind::Outer,
+                DocStyle::RawAttr,
+                "\n"
+            ),
+            Ok("\"a\"".to_string())
+        );
+    }
+    #[test]
+    fn from_block() {
+        assert_eq!(
+            from_doc_comment("/*!\n# a\n\nb\n*/\n", Kind::Inner, DocStyle::Block, "\n"),
+            Ok("# a\n\nb".to_string())
+        );
+    }
+}
@@ -0,0 +1,530 @@
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::markdown::headings;
+use crate::text_pos::to_line;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[non_exhaustive]
+pub enum SelectError {
+    NotFound,
+    Duplicated { lines: Vec<usize> },
+    Unclosed { line: usize },
+}
+
+fn region_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"^[ \t]*<!--[ \t]*(?:region:[ \t]*(.*?)|(endregion)(?::.*?)?)[ \t]*-->[ \t]*\r?$",
+        )
+        .unwrap()
+    })
+}
+
+/// Returns the lines between `<!-- region: {name} -->` and the matching `<!-- endregion -->`.
+///
+/// Regions can be nested. The region marker lines are not included.
+pub fn region(text: &str, name: &str) -> Result<Range<usize>, SelectError> {
+    let mut stack = Vec::new();
+    let mut found = Vec::new();
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let Some(c) = region_regex().captures(line.trim_end_matches('\n')) else {
+            continue;
+        };
+        if let Some(n) = c.get(1) {
+            stack.push((n.as_str(), line_start, offset));
+        } else if let Some((n, start, content_start)) = stack.pop() {
+            if n == name {
+                found.push((start, content_start..line_start));
+            }
+        }
+    }
+    if let Some((_, start, _)) = stack.iter().find(|(n, _, _)| *n == name) {
+        return Err(SelectError::Unclosed {
+            line: to_line(text, *start),
+        });
+    }
+    match found.len() {
+        0 => Err(SelectError::NotFound),
+        1 => Ok(found.pop().unwrap().1),
+        _ => Err(SelectError::Duplicated {
+            lines: found
+                .iter()
+                .map(|(start, _)| to_line(text, *start))
+                .collect(),
+        }),
+    }
+}
+
+/// Returns the section under the heading specified by `path`, up to the next heading of equal or higher level.
+///
+/// `path` is a list of headings separated by `/`, such as `Install/From source`.
+/// Each heading after the first must be in the section of the previous one.
+pub fn section(text: &str, path: &str, include_heading: bool) -> Result<Range<usize>, SelectError> {
+    let headings = &headings(text);
+    let section_end = |index: usize| {
+        headings[index + 1..]
+            .iter()
+            .find(|h| h.level <= headings[index].level)
+            .map_or(text.len(), |h| h.range.start)
+    };
+    let mut names = path.split('/').map(str::trim);
+    let first = names.next().unwrap_or_default();
+    let mut found: Vec<usize> = (0..headings.len())
+        .filter(|&i| headings[i].text == first)
+        .collect();
+    for name in names {
+        found = found
+            .iter()
+            .flat_map(|&i| {
+                let end = section_end(i);
+                let level = headings[i].level;
+                (i + 1..headings.len())
+                    .take_while(move |&j| headings[j].range.start < end)
+                    .filter(move |&j| headings[j].level > level)
+            })
+            .filter(|&j| headings[j].text == name)
+            .collect();
+    }
+    match found.as_slice() {
+        [] => Err(SelectError::NotFound),
+        [i] => {
+            let start = if include_heading {
+                headings[*i].range.start
+            } else {
+                headings[*i].range.end
+            };
+            Ok(start..section_end(*i))
+        }
+        _ => Err(SelectError::Duplicated {
+            lines: found
+                .iter()
+                .map(|&i| to_line(text, headings[i].range.start))
+                .collect(),
+        }),
+    }
+}
+
+fn anchor_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"ANCHOR(_END)?:[ \t]*([A-Za-z0-9_-]+)").unwrap())
+}
+
+/// Returns the lines between `ANCHOR: {name}` and `ANCHOR_END: {name}`, in the style of mdBook.
+///
+/// The anchor lines are not included.
+pub fn anchor(text: &str, name: &str) -> Result<Range<usize>, SelectError> {
+    let name = name.trim_start_matches("ANCHOR:").trim();
+    let mut open = None;
+    let mut found = Vec::new();
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let Some(c) = anchor_regex().captures(line) else {
+            continue;
+        };
+        if &c[2] != name {
+            continue;
+        }
+        if c.get(1).is_none() {
+            open = Some((line_start, offset));
+        } else if let Some((start, content_start)) = open.take() {
+            found.push((start, content_start..line_start));
+        }
+    }
+    if let Some((start, _)) = open {
+        return Err(SelectError::Unclosed {
+            line: to_line(text, start),
+        });
+    }
+    match found.len() {
+        0 => Err(SelectError::NotFound),
+        1 => Ok(found.pop().unwrap().1),
+        _ => Err(SelectError::Duplicated {
+            lines: found
+                .iter()
+                .map(|(start, _)| to_line(text, *start))
+                .collect(),
+        }),
+    }
+}
+
+/// Removes the lines containing `ANCHOR:` or `ANCHOR_END:`.
+pub fn remove_anchor_lines(text: &str) -> String {
+    text.split_inclusive('\n')
+        .filter(|line| !anchor_regex().is_match(line))
+        .collect()
+}
+
+/// Returns the lines in the body of the item whose declaration starts with `signature`, such as `fn main`.
+///
+/// Visibility and qualifiers such as `pub` and `async` before `signature` are ignored.
+pub fn item(text: &str, signature: &str) -> Result<Range<usize>, SelectError> {
+    let words: Vec<_> = signature.split_whitespace().map(regex::escape).collect();
+    let re = Regex::new(&format!(
+        r#"^[ \t]*(?:(?:pub(?:\([^)]*\))?|async|const|unsafe|default|extern(?:[ \t]+"[^"]*")?)[ \t]+)*{}(?:[^A-Za-z0-9_]|$)"#,
+        words.join(r"[ \t]+")
+    ))
+    .unwrap();
+    let mut found = Vec::new();
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        if !re.is_match(line) {
+            continue;
+        }
+        if let Some(body) = item_body(text, line_start) {
+            found.push((line_start, body));
+        }
+    }
+    match found.len() {
+        0 => Err(SelectError::NotFound),
+        1 => Ok(found.pop().unwrap().1),
+        _ => Err(SelectError::Duplicated {
+            lines: found
+                .iter()
+                .map(|(start, _)| to_line(text, *start))
+                .collect(),
+        }),
+    }
+}
+
+/// Returns the range between the braces of the item that starts at `offset`.
+///
+/// The lines of the braces are not included if the braces are alone at the end or start of the line.
+fn item_body(text: &str, offset: usize) -> Option<Range<usize>> {
+    let bytes = text.as_bytes();
+    let mut depth = 0;
+    let mut brackets = 0;
+    let mut open = None;
+    let mut i = offset;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                i = text[i..].find('\n').map_or(bytes.len(), |n| i + n);
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = text[i + 2..]
+                    .find("*/")
+                    .map_or(bytes.len(), |n| i + 2 + n + 2);
+                continue;
+            }
+            b'"' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+            }
+            b'\'' => {
+                if let Some(len) = char_literal_len(&text[i..]) {
+                    i += len;
+                    continue;
+                }
+            }
+            b'(' | b'[' => brackets += 1,
+            b')' | b']' => brackets -= 1,
+            b';' if open.is_none() && brackets == 0 => return None,
+            b'{' => {
+                if open.is_none() {
+                    open = Some(i + 1);
+                }
+                depth += 1;
+            }
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    let start = open?;
+                    let start = match text[start..].find('\n') {
+                        Some(n) if text[start..start + n].trim().is_empty() => start + n + 1,
+                        _ => start,
+                    };
+                    let line_start = text[..i].rfind('\n').map_or(0, |n| n + 1);
+                    let end = if text[line_start..i].trim().is_empty() {
+                        line_start
+                    } else {
+                        i
+                    };
+                    return Some(start..end.max(start));
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Returns the length of the char literal at the start of `s`, or `None` if it is a lifetime.
+fn char_literal_len(s: &str) -> Option<usize> {
+    let mut chars = s.char_indices().skip(1);
+    let (_, c) = chars.next()?;
+    if c == '\\' {
+        s[2..].find('\'').map(|n| n + 3)
+    } else {
+        let (i, c) = chars.next()?;
+        (c == '\'').then_some(i + 1)
+    }
+}
+
+fn hide_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^[ \t]*//[ \t]*hide-(start|end)[ \t]*\r?\n?$").unwrap())
+}
+
+/// Prefixes lines with `# ` so that rustdoc hides them in code blocks.
+///
+/// The lines between `// hide-start` and `// hide-end` are hidden, and the marker lines are removed.
+/// The lines matching `pattern` are also hidden.
+pub fn hide_lines(text: &str, pattern: Option<&Regex>) -> String {
+    let mut s = String::new();
+    let mut hidden = false;
+    for line in text.split_inclusive('\n') {
+        if let Some(c) = hide_regex().captures(line) {
+            hidden = &c[1] == "start";
+            if !line.ends_with('\n') {
+                s.truncate(s.trim_end_matches(['\r', '\n']).len());
+            }
+            continue;
+        }
+        let content = line.trim_end_matches(['\r', '\n']);
+        if hidden || pattern.is_some_and(|p| p.is_match(content)) {
+            s.push('#');
+            if !content.is_empty() {
+                s.push(' ');
+            }
+        }
+        s.push_str(line);
+    }
+    s
+}
+
+/// Removes the indentation common to all non-blank lines.
+///
+/// Only spaces and tabs are treated as indentation.
+pub fn dedent(text: &str) -> String {
+    let mut indent: Option<&str> = None;
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let line_indent = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+        indent = Some(match indent {
+            Some(indent) => {
+                let len = indent
+                    .bytes()
+                    .zip(line_indent.bytes())
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                &indent[..len]
+            }
+            None => line_indent,
+        });
+    }
+    let indent = indent.unwrap_or("");
+    text.split_inclusive('\n')
+        .map(|line| {
+            if line.trim().is_empty() {
+                line.trim_start_matches([' ', '\t'])
+            } else {
+                line.strip_prefix(indent).unwrap_or(line)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(text: &str, name: &str, expected: Result<&str, SelectError>) {
+        assert_eq!(region(text, name).map(|r| &text[r]), expected);
+    }
+
+    #[test]
+    fn region_found() {
+        check(
+            "a\n<!-- region: usage -->\nb\nc\n<!-- endregion -->\nd\n",
+            "usage",
+            Ok("b\nc\n"),
+        );
+    }
+    #[test]
+    fn region_nested() {
+        let text = "<!-- region: a -->\nx\n<!-- region: b -->\ny\n<!-- endregion -->\n<!-- endregion -->\n";
+        check(
+            text,
+            "a",
+            Ok("x\n<!-- region: b -->\ny\n<!-- endregion -->\n"),
+        );
+        check(text, "b", Ok("y\n"));
+    }
+    #[test]
+    fn region_crlf() {
+        check(
+            "<!-- region: a -->\r\nx\r\n<!-- endregion -->\r\n",
+            "a",
+            Ok("x\r\n"),
+        );
+    }
+    #[test]
+    fn region_not_found() {
+        check(
+            "<!-- region: a -->\n<!-- endregion -->\n",
+            "b",
+            Err(SelectError::NotFound),
+        );
+    }
+    #[test]
+    fn region_duplicated() {
+        check(
+            "<!-- region: a -->\n<!-- endregion -->\n<!-- region: a -->\n<!-- endregion -->\n",
+            "a",
+            Err(SelectError::Duplicated { lines: vec![1, 3] }),
+        );
+    }
+    #[test]
+    fn region_unclosed() {
+        check(
+            "x\n<!-- region: a -->\n",
+            "a",
+            Err(SelectError::Unclosed { line: 2 }),
+        );
+    }
+
+    const SECTIONS: &str = "# Title\n\n## Install\n\ntext\n\n### From source\n\nbuild\n\n## Usage\n\nrun\n\n### From source\n\nother\n";
+
+    fn check_section(path: &str, include_heading: bool, expected: Result<&str, SelectError>) {
+        assert_eq!(
+            section(SECTIONS, path, include_heading).map(|r| &SECTIONS[r]),
+            expected
+        );
+    }
+    #[test]
+    fn section_found() {
+        check_section(
+            "Usage",
+            true,
+            Ok("## Usage\n\nrun\n\n### From source\n\nother\n"),
+        );
+        check_section(
+            "Install",
+            false,
+            Ok("\ntext\n\n### From source\n\nbuild\n\n"),
+        );
+    }
+    #[test]
+    fn section_path() {
+        check_section(
+            "Install/From source",
+            true,
+            Ok("### From source\n\nbuild\n\n"),
+        );
+        check_section("Title/Usage/From source", false, Ok("\nother\n"));
+    }
+    #[test]
+    fn section_not_found() {
+        check_section("Usage/Install", true, Err(SelectError::NotFound));
+    }
+    #[test]
+    fn section_duplicated() {
+        check_section(
+            "From source",
+            true,
+            Err(SelectError::Duplicated { lines: vec![7, 15] }),
+        );
+    }
+
+    const EXAMPLE: &str = r#"use std::fmt;
+
+// ANCHOR: setup
+let x = 1;
+// ANCHOR_END: setup
+
+pub(crate) fn helper() -> char {
+    '{'
+}
+
+fn main() {
+    // ANCHOR: body
+    let s = "}";
+    if true {
+        println!("{s}");
+    }
+    // ANCHOR_END: body
+}
+"#;
+
+    fn check_item(signature: &str, expected: Result<&str, SelectError>) {
+        assert_eq!(item(EXAMPLE, signature).map(|r| &EXAMPLE[r]), expected);
+    }
+    #[test]
+    fn item_fn() {
+        check_item(
+            "fn main",
+            Ok("    // ANCHOR: body\n    let s = \"}\";\n    if true {\n        println!(\"{s}\");\n    }\n    // ANCHOR_END: body\n"),
+        );
+        check_item("fn helper", Ok("    '{'\n"));
+    }
+    #[test]
+    fn item_semicolon_in_signature() {
+        let text = "fn bytes() -> [u8; 4] {\n    [0; 4]\n}\n";
+        assert_eq!(item(text, "fn bytes").map(|r| &text[r]), Ok("    [0; 4]\n"));
+        let text = "fn f(a: [u8; 4]);\n";
+        assert_eq!(item(text, "fn f"), Err(SelectError::NotFound));
+    }
+    #[test]
+    fn item_not_found() {
+        check_item("fn mai", Err(SelectError::NotFound));
+        check_item("struct Main", Err(SelectError::NotFound));
+    }
+    #[test]
+    fn anchor_found() {
+        let r = anchor(EXAMPLE, "setup").unwrap();
+        assert_eq!(&EXAMPLE[r], "let x = 1;\n");
+        let r = anchor(EXAMPLE, "ANCHOR: body").unwrap();
+        assert_eq!(
+            dedent(&EXAMPLE[r]),
+            "let s = \"}\";\nif true {\n    println!(\"{s}\");\n}\n"
+        );
+    }
+    #[test]
+    fn dedent_unicode_whitespace() {
+        assert_eq!(dedent(" x();\n\u{a0}y();\n"), " x();\n\u{a0}y();\n");
+        assert_eq!(dedent("\t x();\n\t\ty();\n"), " x();\n\ty();\n");
+    }
+    #[test]
+    fn anchor_unclosed() {
+        assert_eq!(
+            anchor("// ANCHOR: a\n", "a"),
+            Err(SelectError::Unclosed { line: 1 })
+        );
+    }
+    #[test]
+    fn hide_marked_lines() {
+        assert_eq!(
+            hide_lines("// hide-start\nuse a;\n\n// hide-end\nlet x = 1;\n", None),
+            "# use a;\n#\nlet x = 1;\n"
+        );
+    }
+    #[test]
+    fn hide_pattern() {
+        let re = Regex::new("^use ").unwrap();
+        assert_eq!(
+            hide_lines("use a;\nfn main() {\n    use b;\n}", Some(&re)),
+            "# use a;\nfn main() {\n    use b;\n}"
+        );
+    }
+    #[test]
+    fn remove_anchors() {
+        assert_eq!(
+            remove_anchor_lines("a\n// ANCHOR: x\nb\n// ANCHOR_END: x\n"),
+            "a\nb\n"
+        );
+    }
+}
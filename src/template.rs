@@ -0,0 +1,224 @@
+use std::{
+    borrow::Cow,
+    cell::OnceCell,
+    collections::BTreeMap,
+    env,
+    fs::read_to_string,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use regex::Regex;
+
+use crate::fmt::find_similar;
+
+fn placeholder_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(\\)?\{\{[ \t]*([A-Za-z_][A-Za-z0-9_-]*(?:\.[A-Za-z0-9_-]+)*)[ \t]*\}\}")
+            .unwrap()
+    })
+}
+
+/// A `{{name}}` placeholder whose variable is not defined.
+#[derive(Clone, Debug)]
+pub struct UndefinedVariable {
+    pub name: String,
+    /// The range of the placeholder, including the braces.
+    pub range: Range<usize>,
+}
+
+/// The values of the variables in `{{name}}` placeholders of the files included from a directory.
+///
+/// - `{{crate.{key}}}` is the string value of `key` in `[package]` of the nearest `Cargo.toml`.
+/// - `{{env.{name}}}` is the value of the environment variable `name`.
+///
+/// Variables defined explicitly take precedence over both.
+/// Placeholders of other names are left as is, so that `{{` in code such as `format!("{{x}}")` is not affected.
+pub struct Variables<'a> {
+    defines: &'a BTreeMap<String, String>,
+    base: &'a Path,
+    package: OnceCell<Option<Package>>,
+}
+
+struct Package {
+    dir: PathBuf,
+    table: toml::Table,
+}
+
+impl<'a> Variables<'a> {
+    pub fn new(defines: &'a BTreeMap<String, String>, base: &'a Path) -> Self {
+        Self {
+            defines,
+            base,
+            package: OnceCell::new(),
+        }
+    }
+
+    /// Returns `true` if `name` is defined or in the `crate` or `env` namespace,
+    /// so that a placeholder of it must be substituted.
+    fn is_variable(&self, name: &str) -> bool {
+        self.defines.contains_key(name) || name.starts_with("crate.") || name.starts_with("env.")
+    }
+
+    pub fn get(&self, name: &str) -> Option<Cow<'_, str>> {
+        if let Some(value) = self.defines.get(name) {
+            return Some(Cow::Borrowed(value));
+        }
+        if let Some(name) = name.strip_prefix("env.") {
+            return env::var(name).ok().map(Cow::Owned);
+        }
+        let key = name.strip_prefix("crate.")?;
+        let package = self.package()?;
+        match package.table.get(key)? {
+            toml::Value::String(value) => Some(Cow::Borrowed(value)),
+            toml::Value::Table(t) if t.get("workspace") == Some(&toml::Value::Boolean(true)) => {
+                workspace_value(&package.dir, key).map(Cow::Owned)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the defined variable most similar to `name`, if it is similar enough to be a typo.
+    pub fn find_similar(&self, name: &str) -> Option<String> {
+        let mut names: Vec<_> = self.defines.keys().cloned().collect();
+        if let Some(package) = self.package() {
+            names.extend(package.table.keys().map(|key| format!("crate.{key}")));
+        }
+        find_similar(name, names.iter().map(String::as_str)).map(str::to_string)
+    }
+
+    fn package(&self) -> Option<&Package> {
+        self.package
+            .get_or_init(|| find_package(self.base))
+            .as_ref()
+    }
+}
+
+/// Returns `[package]` of the nearest `Cargo.toml` in `dir` or its ancestors.
+fn find_package(dir: &Path) -> Option<Package> {
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    for dir in dir.ancestors() {
+        let Some(mut manifest) = read_manifest(dir) else {
+            continue;
+        };
+        if let Some(toml::Value::Table(table)) = manifest.remove("package") {
+            return Some(Package {
+                dir: dir.to_path_buf(),
+                table,
+            });
+        }
+    }
+    None
+}
+
+/// Returns the string value of `key` in `[workspace.package]` of the workspace containing `dir`,
+/// for keys specified as `{key}.workspace = true`.
+fn workspace_value(dir: &Path, key: &str) -> Option<String> {
+    for dir in dir.ancestors() {
+        let Some(manifest) = read_manifest(dir) else {
+            continue;
+        };
+        if let Some(workspace) = manifest.get("workspace") {
+            return workspace
+                .get("package")?
+                .get(key)?
+                .as_str()
+                .map(str::to_string);
+        }
+    }
+    None
+}
+
+fn read_manifest(dir: &Path) -> Option<toml::Table> {
+    let text = read_to_string(dir.join("Cargo.toml")).ok()?;
+    toml::from_str(&text).ok()
+}
+
+/// Replaces the `{{name}}` placeholders in `text` with the values of the variables.
+///
+/// A placeholder preceded by `\` is written without the `\` instead of being replaced.
+pub fn substitute<'t>(text: &'t str, vars: &Variables) -> Result<Cow<'t, str>, UndefinedVariable> {
+    let mut s = String::new();
+    let mut last = 0;
+    for c in placeholder_regex().captures_iter(text) {
+        let m = c.get(0).unwrap();
+        let name = &c[2];
+        if !vars.is_variable(name) {
+            continue;
+        }
+        s.push_str(&text[last..m.start()]);
+        if c.get(1).is_some() {
+            s.push_str(&m.as_str()[1..]);
+        } else if let Some(value) = vars.get(name) {
+            s.push_str(&value);
+        } else {
+            return Err(UndefinedVariable {
+                name: name.to_string(),
+                range: m.range(),
+            });
+        }
+        last = m.end();
+    }
+    if last == 0 {
+        return Ok(Cow::Borrowed(text));
+    }
+    s.push_str(&text[last..]);
+    Ok(Cow::Owned(s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substitute_variables() {
+        let defines = BTreeMap::from([("msrv".to_string(), "1.70".to_string())]);
+        let vars = Variables::new(&defines, Path::new("./tests/data"));
+        assert_eq!(
+            substitute("`{{crate.name}}` requires Rust {{ msrv }}.", &vars).unwrap(),
+            "`rustdoc-include` requires Rust 1.70."
+        );
+        assert!(matches!(
+            substitute("format!(\"{{}}\")", &vars),
+            Ok(Cow::Borrowed(_))
+        ));
+    }
+    #[test]
+    fn substitute_only_variables() {
+        let defines = BTreeMap::new();
+        let vars = Variables::new(&defines, Path::new("./tests/data"));
+        let text = "```rust\nlet name = \"a\";\nprintln!(\"{}\", format!(\"{{name}}\"));\n```\n";
+        assert!(matches!(substitute(text, &vars), Ok(Cow::Borrowed(_))));
+    }
+    #[test]
+    fn substitute_escaped() {
+        let defines = BTreeMap::new();
+        let vars = Variables::new(&defines, Path::new("./tests/data"));
+        assert_eq!(
+            substitute(r"\{{crate.name}} is {{crate.name}}", &vars).unwrap(),
+            "{{crate.name}} is rustdoc-include"
+        );
+        assert_eq!(
+            substitute(r"\{{crate.verison}}", &vars).unwrap(),
+            "{{crate.verison}}"
+        );
+    }
+    #[test]
+    fn substitute_env() {
+        let defines = BTreeMap::new();
+        let vars = Variables::new(&defines, Path::new("."));
+        let path = env::var("PATH").unwrap();
+        assert_eq!(substitute("{{env.PATH}}", &vars).unwrap(), path);
+    }
+    #[test]
+    fn undefined_variable() {
+        let defines = BTreeMap::new();
+        let vars = Variables::new(&defines, Path::new("."));
+        let e = substitute("a {{crate.verison}} b", &vars).unwrap_err();
+        assert_eq!(e.name, "crate.verison");
+        assert_eq!(e.range, 2..19);
+        assert_eq!(vars.find_similar(&e.name).as_deref(), Some("crate.version"));
+    }
+}
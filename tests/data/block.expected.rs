@@ -0,0 +1,8 @@
+// #[include_doc("doc_quote.md", start, style("block"))]
+/**
+# Greeting
+
+Prints "hello" followed by `\n`.
+*/
+// #[include_doc("doc_quote.md", end)]
+fn main() {}
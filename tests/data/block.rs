@@ -0,0 +1,3 @@
+// #[include_doc("doc_quote.md", start, style("block"))]
+// #[include_doc("doc_quote.md", end)]
+fn main() {}
@@ -0,0 +1,7 @@
+// #[include_doc("example.toml", start, code)]
+/// ```toml
+/// [package]
+/// name = "example"
+/// ```
+// #[include_doc("example.toml", end)]
+fn main() {}
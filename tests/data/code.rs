@@ -0,0 +1,3 @@
+// #[include_doc("example.toml", start, code)]
+// #[include_doc("example.toml", end)]
+fn main() {}
@@ -0,0 +1,10 @@
+// #[include_doc("examples/hello.rs", start(4), code)]
+/// ```rust
+///     // ANCHOR: setup
+///     let mut map = HashMap::new();
+///     map.insert("a", 1);
+///     // ANCHOR_END: setup
+///     println!("{map:?}");
+/// ```
+// #[include_doc("examples/hello.rs", end(-2))]
+fn main() {}
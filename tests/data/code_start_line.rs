@@ -0,0 +1,3 @@
+// #[include_doc("examples/hello.rs", start(4), code)]
+// #[include_doc("examples/hello.rs", end(-2))]
+fn main() {}
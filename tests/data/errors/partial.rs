@@ -0,0 +1,7 @@
+// #[include_doc("../doc.md", start)]
+// #[include_doc("../doc.md", end)]
+fn a() {}
+
+// #[include_doc("../not_exists.md", start)]
+// #[include_doc("../not_exists.md", end)]
+fn b() {}
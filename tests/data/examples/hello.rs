@@ -0,0 +1,9 @@
+use std::collections::HashMap;
+
+fn main() {
+    // ANCHOR: setup
+    let mut map = HashMap::new();
+    map.insert("a", 1);
+    // ANCHOR_END: setup
+    println!("{map:?}");
+}
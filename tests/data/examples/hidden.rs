@@ -0,0 +1,10 @@
+use std::collections::HashMap;
+
+// hide-start
+fn main() {
+// hide-end
+    let mut map = HashMap::new();
+    map.insert("a", 1);
+// hide-start
+}
+// hide-end
@@ -0,0 +1,5 @@
+// #[include_doc("doc_fence.md", start("```rust"))]
+/// ```rust
+/// fn main() {}
+// #[include_doc("doc_fence.md", end("```"))]
+fn main() {}
@@ -0,0 +1,3 @@
+// #[include_doc("doc_fence.md", start("```rust"))]
+// #[include_doc("doc_fence.md", end("```"))]
+fn main() {}
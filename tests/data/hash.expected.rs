@@ -0,0 +1,4 @@
+// #[include_doc("doc.md", start)]
+/// this is example function.
+// #[include_doc("doc.md", end, hash("d7954c6522ad816e"))]
+fn main() {}
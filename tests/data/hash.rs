@@ -0,0 +1,3 @@
+// #[include_doc("doc.md", start)]
+// #[include_doc("doc.md", end, hash("cbf29ce484222325"))]
+fn main() {}
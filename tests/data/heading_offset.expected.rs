@@ -0,0 +1,12 @@
+// #[include_doc("doc_sections.md", start, heading_offset(1))]
+/// ## Title
+///
+/// ### Install
+///
+/// cargo install
+///
+/// ### Usage
+///
+/// run it
+// #[include_doc("doc_sections.md", end)]
+fn main() {}
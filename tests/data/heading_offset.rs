@@ -0,0 +1,3 @@
+// #[include_doc("doc_sections.md", start, heading_offset(1))]
+// #[include_doc("doc_sections.md", end)]
+fn main() {}
@@ -0,0 +1,11 @@
+// #[include_doc("examples/hidden.rs", start, code, hide("^use "))]
+/// ```rust
+/// # use std::collections::HashMap;
+///
+/// # fn main() {
+///     let mut map = HashMap::new();
+///     map.insert("a", 1);
+/// # }
+/// ```
+// #[include_doc("examples/hidden.rs", end)]
+fn main() {}
@@ -0,0 +1,3 @@
+// #[include_doc("examples/hidden.rs", start, code, hide("^use "))]
+// #[include_doc("examples/hidden.rs", end)]
+fn main() {}
@@ -0,0 +1,14 @@
+struct S;
+impl S {
+    // #[include_doc("doc_quote.md", start)]
+    /// # Greeting
+    ///
+    /// Prints "hello" followed by `\n`.
+    // #[include_doc("doc_quote.md", end)]
+    fn f() {}
+}
+mod m {
+	// #![include_doc("doc.md", start)]
+	//! this is example function.
+	// #![include_doc("doc.md", end)]
+}
@@ -0,0 +1,10 @@
+struct S;
+impl S {
+    // #[include_doc("doc_quote.md", start)]
+    // #[include_doc("doc_quote.md", end)]
+    fn f() {}
+}
+mod m {
+	// #![include_doc("doc.md", start)]
+	// #![include_doc("doc.md", end)]
+}
@@ -0,0 +1,9 @@
+struct S;
+impl S {
+    // #[include_doc("doc_crlf.md", start)]
+    /// A
+    /// B
+    /// C
+    // #[include_doc("doc_crlf.md", end)]
+    fn f() {}
+}
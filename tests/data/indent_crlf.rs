@@ -0,0 +1,6 @@
+struct S;
+impl S {
+    // #[include_doc("doc_crlf.md", start)]
+    // #[include_doc("doc_crlf.md", end)]
+    fn f() {}
+}
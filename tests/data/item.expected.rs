@@ -0,0 +1,14 @@
+// #[include_doc("examples/hello.rs", item("fn main"), code)]
+/// ```rust
+/// let mut map = HashMap::new();
+/// map.insert("a", 1);
+/// println!("{map:?}");
+/// ```
+// #[include_doc("examples/hello.rs", end)]
+// #[include_doc("examples/hello.rs", anchor("setup"), code)]
+/// ```rust
+/// let mut map = HashMap::new();
+/// map.insert("a", 1);
+/// ```
+// #[include_doc("examples/hello.rs", end)]
+fn main() {}
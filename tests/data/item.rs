@@ -0,0 +1,5 @@
+// #[include_doc("examples/hello.rs", item("fn main"), code)]
+// #[include_doc("examples/hello.rs", end)]
+// #[include_doc("examples/hello.rs", anchor("setup"), code)]
+// #[include_doc("examples/hello.rs", end)]
+fn main() {}
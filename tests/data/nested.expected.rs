@@ -0,0 +1,8 @@
+// #[include_doc("doc_nested.md", start)]
+/// Nested
+///
+/// example
+///
+/// End
+// #[include_doc("doc_nested.md", end)]
+fn main() {}
@@ -0,0 +1,3 @@
+// #[include_doc("doc_nested.md", start)]
+// #[include_doc("doc_nested.md", end)]
+fn main() {}
@@ -0,0 +1,4 @@
+// #[include_doc("doc_region.md", region("usage"))]
+/// Run `cargo test`.
+// #[include_doc("doc_region.md", end)]
+fn main() {}
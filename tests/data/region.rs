@@ -0,0 +1,3 @@
+// #[include_doc("doc_region.md", region("usage"))]
+// #[include_doc("doc_region.md", end)]
+fn main() {}
@@ -0,0 +1,4 @@
+// #[include_doc("doc_sections.md", section("Install"), exclude_heading)]
+/// cargo install
+// #[include_doc("doc_sections.md", end)]
+fn main() {}
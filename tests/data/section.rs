@@ -0,0 +1,3 @@
+// #[include_doc("doc_sections.md", section("Install"), exclude_heading)]
+// #[include_doc("doc_sections.md", end)]
+fn main() {}
@@ -0,0 +1,16 @@
+// #[include_doc("doc_quote.md", start, style("attr"))]
+#[doc = " # Greeting"]
+#[doc = ""]
+#[doc = " Prints \"hello\" followed by `\\n`."]
+// #[include_doc("doc_quote.md", end)]
+fn main() {}
+
+mod a {
+    // #![include_doc("doc_quote.md", start, style("raw"))]
+    #![doc = r#"
+# Greeting
+
+Prints "hello" followed by `\n`.
+    "#]
+    // #![include_doc("doc_quote.md", end)]
+}
@@ -0,0 +1,8 @@
+// #[include_doc("doc_quote.md", start, style("attr"))]
+// #[include_doc("doc_quote.md", end)]
+fn main() {}
+
+mod a {
+    // #![include_doc("doc_quote.md", start, style("raw"))]
+    // #![include_doc("doc_quote.md", end)]
+}
@@ -0,0 +1,13 @@
+// #[include_doc("doc_variables.md", start)]
+/// # rustdoc-include
+///
+/// Licensed under MIT OR Apache-2.0.
+///
+/// ```rust
+/// println!("{{}}", 1);
+/// let s = format!("{{name}}");
+/// ```
+///
+/// Write `{{crate.name}}` for the name.
+// #[include_doc("doc_variables.md", end)]
+fn main() {}
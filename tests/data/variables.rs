@@ -0,0 +1,3 @@
+// #[include_doc("doc_variables.md", start)]
+// #[include_doc("doc_variables.md", end)]
+fn main() {}